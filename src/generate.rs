@@ -0,0 +1,180 @@
+// QR码生成模块：与 detect 系列接口对称，提供编码能力
+use base64::prelude::*;
+use image::{Luma, ImageBuffer};
+use qrcode::{Color, EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+
+/// 标准二维码纠错等级：L(7%) < M(15%) < Q(25%) < H(30%)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ErrorCorrection {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl Default for ErrorCorrection {
+    fn default() -> Self {
+        ErrorCorrection::L
+    }
+}
+
+impl From<ErrorCorrection> for EcLevel {
+    fn from(value: ErrorCorrection) -> Self {
+        match value {
+            ErrorCorrection::L => EcLevel::L,
+            ErrorCorrection::M => EcLevel::M,
+            ErrorCorrection::Q => EcLevel::Q,
+            ErrorCorrection::H => EcLevel::H,
+        }
+    }
+}
+
+fn default_size() -> u32 {
+    256
+}
+
+fn default_margin() -> u32 {
+    4
+}
+
+fn default_format() -> String {
+    "base64".to_string()
+}
+
+/// `size`/`margin` 上限：两者都直接喂进位图渲染器的像素/模块计算，不做限制的话一个
+/// `size: 4000000000` 这样的请求就能让 `image` crate 去分配一张天文数字像素的
+/// `ImageBuffer`，直接把进程的分配器干崩——所以在渲染前就拒绝掉不合理的取值
+const MAX_SIZE: u32 = 4096;
+const MAX_MARGIN: u32 = 64;
+
+fn validate_dimensions(req: &GenerateRequest) -> Result<(), String> {
+    if req.size == 0 || req.size > MAX_SIZE {
+        return Err(format!("size must be between 1 and {} pixels", MAX_SIZE));
+    }
+    if req.margin > MAX_MARGIN {
+        return Err(format!("margin must be at most {} modules", MAX_MARGIN));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GenerateRequest {
+    pub text: String,
+    #[serde(default)]
+    pub error_correction: ErrorCorrection,
+    #[serde(default = "default_size")]
+    pub size: u32,
+    #[serde(default = "default_margin")]
+    pub margin: u32,
+    #[serde(default = "default_format")]
+    pub format: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GenerateResponse {
+    pub success: bool,
+    pub message: String,
+    pub format: String,
+    pub content_type: String,
+    pub image_base64: Option<String>,
+}
+
+/// 渲染结果：字节内容 + 用于 HTTP 响应的 content-type
+pub struct RenderedImage {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+/// 生成二维码并按目标尺寸/边距渲染为 PNG 字节
+pub fn render_png(req: &GenerateRequest) -> Result<RenderedImage, String> {
+    validate_dimensions(req)?;
+
+    let code = QrCode::with_error_correction_level(req.text.as_bytes(), req.error_correction.into())
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+
+    let modules_per_side = code.width() as u32 + 2 * req.margin;
+    let scale = (req.size / modules_per_side).max(1);
+    // 渲染器自带的静区固定为 4 个模块，这里关掉它，按 `margin` 自己补白边
+    let symbol: ImageBuffer<Luma<u8>, Vec<u8>> = code
+        .render::<Luma<u8>>()
+        .quiet_zone(false)
+        .module_dimensions(scale, scale)
+        .build();
+    let pad = req.margin * scale;
+    let mut image = ImageBuffer::from_pixel(symbol.width() + 2 * pad, symbol.height() + 2 * pad, Luma([255u8]));
+    for (x, y, pixel) in symbol.enumerate_pixels() {
+        image.put_pixel(x + pad, y + pad, *pixel);
+    }
+
+    let mut bytes = Vec::new();
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+
+    Ok(RenderedImage {
+        bytes,
+        content_type: "image/png",
+    })
+}
+
+/// 生成二维码并渲染为 SVG：按暗模块拼出一个单一 `<path>`，比逐模块 `<rect>` 文件更小，
+/// 边框（静区）默认 4 个模块，`viewBox` 按 `moduleCount + 2*margin` 取整数坐标，天然分辨率无关
+pub fn render_svg(req: &GenerateRequest) -> Result<RenderedImage, String> {
+    validate_dimensions(req)?;
+
+    let code = QrCode::with_error_correction_level(req.text.as_bytes(), req.error_correction.into())
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+
+    let width = code.width();
+    let side = width as u32 + 2 * req.margin;
+    let colors = code.to_colors();
+
+    let mut path = String::new();
+    for y in 0..width {
+        for x in 0..width {
+            if colors[y * width + x] == Color::Dark {
+                let px = x as u32 + req.margin;
+                let py = y as u32 + req.margin;
+                path.push_str(&format!("M{},{}h1v1h-1z", px, py));
+            }
+        }
+    }
+
+    let svg = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {side} {side}\" shape-rendering=\"crispEdges\">\
+         <rect width=\"{side}\" height=\"{side}\" fill=\"white\"/>\
+         <path d=\"{path}\" fill=\"black\"/>\
+         </svg>",
+        side = side,
+        path = path,
+    );
+
+    Ok(RenderedImage {
+        bytes: svg.into_bytes(),
+        content_type: "image/svg+xml",
+    })
+}
+
+/// 按 `format` 分发到对应的渲染器：`png`/`base64` 走位图，`svg` 走矢量图
+pub fn render(req: &GenerateRequest) -> Result<RenderedImage, String> {
+    match req.format.as_str() {
+        "png" | "base64" => render_png(req),
+        "svg" => render_svg(req),
+        other => Err(format!("Unsupported format: {}", other)),
+    }
+}
+
+/// 根据请求中的 `format` 字段分发渲染，支持 `png`/`base64`/`svg`
+pub fn generate(req: &GenerateRequest) -> Result<GenerateResponse, String> {
+    let rendered = render(req)?;
+    Ok(GenerateResponse {
+        success: true,
+        message: "QR code generated".to_string(),
+        format: req.format.clone(),
+        content_type: rendered.content_type.to_string(),
+        image_base64: Some(BASE64_STANDARD.encode(&rendered.bytes)),
+    })
+}
@@ -1,13 +1,14 @@
 use axum::{
-    extract::{Multipart, Query, WebSocketUpgrade, ws::{WebSocket, Message}},
+    extract::{Multipart, Path, Query, WebSocketUpgrade, ws::{WebSocket, Message}},
     http::StatusCode,
-    response::{Html, Json, Response, Redirect},
+    response::{Html, Json, Response, Redirect, IntoResponse, sse::{Event, KeepAlive, Sse}},
     routing::{get, post},
     Router,
 };
 use opencv::{
     core::{Mat, Vector},
     imgcodecs::{IMREAD_COLOR},
+    objdetect::QRCodeDetector as ClassicQRCodeDetector,
     wechat_qrcode::WeChatQRCode,
     prelude::*,
 };
@@ -15,15 +16,34 @@ use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf, time::Instant};
 use tower_http::cors::CorsLayer;
 use tracing::{info, warn, error, debug};
-use object_pool::Pool;
+use object_pool::{Pool, Reusable};
 use futures_util::{SinkExt, StreamExt};
 use base64::prelude::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+mod generate;
+use generate::{GenerateRequest, generate as generate_qr};
+
+mod qrencode;
+use qrencode::{EncodeRequest, encode as encode_qr};
+
+mod payload;
+use payload::ParsedPayload;
+
+mod scan;
+
+mod session;
+
+mod structured_append;
+
+#[derive(Debug, Serialize)]
 struct QRCodeResult {
     text: String,
     points: Vec<[f32; 2]>,
     bbox: BoundingBox,
+    // 仅在请求开启 parse 时才会被填充：HTTP 的 ?parse=true，或批量/WebSocket 请求里的
+    // `parse: true`；命令行 scan 子命令总是填充
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<ParsedPayload>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,6 +62,7 @@ struct DetectionStatistics {
     image_width: i32,
     image_height: i32,
     pool_acquisition_time_ms: f64,
+    backend: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -62,6 +83,28 @@ struct DetectionResponse {
     qrcodes: Vec<QRCodeResult>,
     count: usize,
     statistics: DetectionStatistics,
+    // 仅在 `qrcodes` 里凑齐了一套本服务自定义 Structured Append 头部的分片时才会填充，
+    // 见 `structured_append` 模块
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reassembled: Option<String>,
+}
+
+// 从一组 `QRCodeResult` 的解码文本里尝试重组 Structured Append 序列；缺片时记录日志并返回 None
+fn reassemble_qrcodes(qrcodes: &[QRCodeResult]) -> Option<String> {
+    let texts: Vec<String> = qrcodes.iter().map(|r| r.text.clone()).collect();
+    reassemble_texts(&texts)
+}
+
+// 跟 `reassemble_qrcodes` 共用的底层实现，直接接受一组解码文本——用于跨多张图片
+// （`/detect/batch`）聚合重组，而不仅限于单张图片内部的 `qrcodes`
+fn reassemble_texts(texts: &[String]) -> Option<String> {
+    match structured_append::reassemble(texts)? {
+        structured_append::Outcome::Complete(text) => Some(text),
+        structured_append::Outcome::Incomplete { total, missing } => {
+            warn!("Structured-append sequence incomplete: missing indices {:?} of {}", missing, total);
+            None
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,6 +112,15 @@ struct WebSocketRequest {
     #[serde(rename = "type")]
     msg_type: String,
     image: Option<String>, // Base64 编码的图片数据
+    #[serde(default)]
+    parse: bool, // 为 true 时在 "detect"/"detect_batch" 结果中附带结构化 payload
+    images: Option<Vec<String>>, // 仅用于 "detect_batch" 消息类型：多张 Base64 图片
+    // 以下字段仅用于 "generate" 消息类型
+    text: Option<String>,
+    error_correction: Option<generate::ErrorCorrection>,
+    size: Option<u32>,
+    margin: Option<u32>,
+    format: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -81,6 +133,8 @@ struct WebSocketResponse {
     count: Option<usize>,
     statistics: Option<DetectionStatistics>,
     error: Option<String>,
+    image_base64: Option<String>,
+    results: Option<Vec<DetectionResponse>>, // 仅用于 "detect_batch" 结果
 }
 
 #[derive(Debug, Deserialize)]
@@ -89,143 +143,327 @@ struct HealthQuery {
     verbose: bool,
 }
 
+/// 选择的检测后端：`wechat`（默认，需要四个 Caffe 模型文件，缺失时自动降级到 `rqrr`）、
+/// `classic`（OpenCV 内置的无神经网络检测器，零外部资源，支持一帧多码）或
+/// `rqrr`（纯 Rust 解码器，不依赖 OpenCV 的 DNN 模块，也不需要模型文件）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DetectorBackendKind {
+    WeChat,
+    Classic,
+    PureRust,
+}
+
+impl DetectorBackendKind {
+    fn label(&self) -> &'static str {
+        match self {
+            DetectorBackendKind::WeChat => "wechat",
+            DetectorBackendKind::Classic => "classic",
+            DetectorBackendKind::PureRust => "rqrr",
+        }
+    }
+}
+
+fn detector_backend_kind() -> DetectorBackendKind {
+    match std::env::var("DETECTOR_BACKEND").unwrap_or_else(|_| "wechat".to_string()).to_lowercase().as_str() {
+        "classic" => DetectorBackendKind::Classic,
+        "rqrr" | "purerust" | "pure_rust" => DetectorBackendKind::PureRust,
+        _ => DetectorBackendKind::WeChat,
+    }
+}
+
+const WECHAT_MODEL_FILES: [&str; 4] = [
+    "models/detect.prototxt",
+    "models/detect.caffemodel",
+    "models/sr.prototxt",
+    "models/sr.caffemodel",
+];
+
+fn wechat_model_files_present() -> bool {
+    WECHAT_MODEL_FILES.iter().all(|f| PathBuf::from(f).exists())
+}
+
+/// 实际生效的后端：请求的是 `wechat` 但模型文件缺失时，自动降级到零依赖的 `rqrr` 后端，
+/// 而不是像过去那样直接拒绝启动
+fn effective_backend_kind() -> DetectorBackendKind {
+    let requested = detector_backend_kind();
+    if requested == DetectorBackendKind::WeChat && !wechat_model_files_present() {
+        warn!("WeChat QRCode model files not found, falling back to the pure-Rust rqrr backend");
+        return DetectorBackendKind::PureRust;
+    }
+    requested
+}
+
+enum DetectorBackend {
+    WeChat(WeChatQRCode),
+    Classic(ClassicQRCodeDetector),
+    PureRust,
+}
+
 struct QRCodeDetector {
-    detector: WeChatQRCode,
+    backend: DetectorBackend,
 }
 
 impl QRCodeDetector {
     fn new() -> opencv::Result<Self> {
-        // WeChat QRCode模型文件路径
-        let detector_prototxt = "models/detect.prototxt";
-        let detector_caffemodel = "models/detect.caffemodel";
-        let super_resolution_prototxt = "models/sr.prototxt";
-        let super_resolution_caffemodel = "models/sr.caffemodel";
-        
-        let detector = WeChatQRCode::new(
-            detector_prototxt,
-            detector_caffemodel,
-            super_resolution_prototxt,
-            super_resolution_caffemodel,
-        )?;
+        let backend = match effective_backend_kind() {
+            DetectorBackendKind::WeChat => {
+                // WeChat QRCode模型文件路径
+                let detector_prototxt = "models/detect.prototxt";
+                let detector_caffemodel = "models/detect.caffemodel";
+                let super_resolution_prototxt = "models/sr.prototxt";
+                let super_resolution_caffemodel = "models/sr.caffemodel";
+
+                let detector = WeChatQRCode::new(
+                    detector_prototxt,
+                    detector_caffemodel,
+                    super_resolution_prototxt,
+                    super_resolution_caffemodel,
+                )?;
+
+                DetectorBackend::WeChat(detector)
+            }
+            DetectorBackendKind::Classic => {
+                DetectorBackend::Classic(ClassicQRCodeDetector::default()?)
+            }
+            DetectorBackendKind::PureRust => DetectorBackend::PureRust,
+        };
 
-        Ok(Self { detector })
+        Ok(Self { backend })
     }
 
-    fn detect_qr_codes(&mut self, image: &Mat) -> Result<Vec<QRCodeResult>, Box<dyn std::error::Error>> {
-        let mut points = Vector::<Mat>::new();
-        
-        let decoded_info = self.detector.detect_and_decode(image, &mut points)?;
-        
-        if decoded_info.is_empty() {
-            return Ok(vec![]);
+    fn detect_qr_codes(&mut self, image: &Mat, parse_payload: bool) -> Result<Vec<QRCodeResult>, Box<dyn std::error::Error>> {
+        match &mut self.backend {
+            DetectorBackend::WeChat(detector) => {
+                let mut points = Vector::<Mat>::new();
+                let decoded_info = detector.detect_and_decode(image, &mut points)?;
+                opencv_results_to_qr_results(&decoded_info, &points, image, parse_payload)
+            }
+            DetectorBackend::Classic(detector) => {
+                let mut decoded_info = Vector::<String>::new();
+                let mut points = Vector::<Mat>::new();
+                let mut straight_qrcode = Vector::<Mat>::new();
+                detector.detect_and_decode_multi(image, &mut decoded_info, &mut points, &mut straight_qrcode)?;
+                opencv_results_to_qr_results(&decoded_info, &points, image, parse_payload)
+            }
+            DetectorBackend::PureRust => detect_qr_codes_rqrr(image, parse_payload),
         }
-        
-        let mut results = Vec::new();
-        
-        for i in 0..decoded_info.len() {
-            let text = decoded_info.get(i)?;
-            
-            // 提取四个角点坐标
-            if i < points.len() {
-                let point_mat = points.get(i)?;
-                
-                // WeChat QRCode返回的points是每个QR码的四个角点
-                // point_mat 是一个 8x1 的矩阵，包含 4 个点的 x,y 坐标
-                // 格式: [x1, y1, x2, y2, x3, y3, x4, y4]
-                let mut coordinates = Vec::new();
-                
-                if point_mat.rows() >= 4 && point_mat.cols() >= 2 {
-                    // 读取四个角点
-                    for j in 0..4 {
-                        let x = (*point_mat.at_2d::<f32>(j, 0)? * 10.0).round() / 10.0;  // 保留1位小数
-                        let y = (*point_mat.at_2d::<f32>(j, 1)? * 10.0).round() / 10.0;  // 保留1位小数
-                        coordinates.push([x, y]);
-                    }
-                } else if point_mat.total() >= 8 {
-                    // 如果是8x1矩阵，按顺序读取
-                    for j in 0..4 {
-                        let x = (*point_mat.at::<f32>(j * 2)? * 10.0).round() / 10.0;      // 保留1位小数
-                        let y = (*point_mat.at::<f32>(j * 2 + 1)? * 10.0).round() / 10.0;  // 保留1位小数
-                        coordinates.push([x, y]);
-                    }
-                } else {
-                    // 如果角点数据无效，创建一个基于图片尺寸的默认区域
-                    warn!("Invalid corner points data for QR code {}, using fallback", i);
-                    let img_width = image.cols() as f32;
-                    let img_height = image.rows() as f32;
-                    
-                    let size = (img_width.min(img_height) * 0.6) as f32;
-                    let x_offset = (img_width - size) / 2.0;
-                    let y_offset = (img_height - size) / 2.0;
-                    
-                    coordinates = vec![
-                        [x_offset, y_offset],
-                        [x_offset + size, y_offset],
-                        [x_offset + size, y_offset + size],
-                        [x_offset, y_offset + size],
-                    ];
-                }
-                
-                // 计算边界框
-                if !coordinates.is_empty() {
-                    let min_x = coordinates.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min);
-                    let max_x = coordinates.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max);
-                    let min_y = coordinates.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min);
-                    let max_y = coordinates.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max);
-                    
-                    // 边界框坐标也保留1位小数
-                    let bbox_x = (min_x * 10.0).round() / 10.0;
-                    let bbox_y = (min_y * 10.0).round() / 10.0;
-                    let bbox_width = ((max_x - min_x) * 10.0).round() / 10.0;
-                    let bbox_height = ((max_y - min_y) * 10.0).round() / 10.0;
-                    
-                    results.push(QRCodeResult {
-                        text,
-                        points: coordinates,
-                        bbox: BoundingBox {
-                            x: bbox_x,
-                            y: bbox_y,
-                            width: bbox_width,
-                            height: bbox_height,
-                        },
-                    });
+    }
+}
+
+/// 把 OpenCV 后端（WeChat/Classic）的解码结果转换为统一的 `QRCodeResult` 列表
+fn opencv_results_to_qr_results(
+    decoded_info: &Vector<String>,
+    points: &Vector<Mat>,
+    image: &Mat,
+    parse_payload: bool,
+) -> Result<Vec<QRCodeResult>, Box<dyn std::error::Error>> {
+    if decoded_info.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut results = Vec::new();
+
+    for i in 0..decoded_info.len() {
+        let text = decoded_info.get(i)?;
+        match extract_corner_points(points, i, image) {
+            Ok(coordinates) => results.push(build_qr_result(text, coordinates, parse_payload)),
+            // 一个码的角点数据缺失/不合规不该拖累同一张图里其它已经成功解出的码，
+            // 丢掉这一个、继续处理剩下的（跟 detect_qr_codes_rqrr 里的处理方式一致）
+            Err(e) => warn!("Skipping QR code {} due to corner point extraction failure: {}", i, e),
+        }
+    }
+
+    Ok(results)
+}
+
+/// 纯 Rust 解码路径：把 OpenCV `Mat` 转成灰度图喂给 `rqrr`，不依赖 DNN 模型，也不需要 OpenCV
+/// 的 WeChat/Classic 检测器；一帧里的多个码都会被 `detect_grids` 一并找到
+fn detect_qr_codes_rqrr(image: &Mat, parse_payload: bool) -> Result<Vec<QRCodeResult>, Box<dyn std::error::Error>> {
+    let mut gray = Mat::default();
+    opencv::imgproc::cvt_color(image, &mut gray, opencv::imgproc::COLOR_BGR2GRAY, 0)?;
+
+    let size = gray.size()?;
+    let width = size.width as u32;
+    let height = size.height as u32;
+    let bytes = gray.data_bytes()?.to_vec();
+
+    let gray_image = image::GrayImage::from_raw(width, height, bytes)
+        .ok_or("Failed to build grayscale image for rqrr")?;
+
+    let mut prepared = rqrr::PreparedImage::prepare(gray_image);
+    let grids = prepared.detect_grids();
+
+    let mut results = Vec::new();
+    for grid in grids {
+        let coordinates: Vec<[f32; 2]> = grid
+            .bounds
+            .iter()
+            .map(|p| [p.x as f32, p.y as f32])
+            .collect();
+
+        match grid.decode() {
+            Ok((_meta, text)) => results.push(build_qr_result(text, coordinates, parse_payload)),
+            Err(e) => warn!("rqrr failed to decode a detected grid: {}", e),
+        }
+    }
+
+    Ok(results)
+}
+
+/// 读取第 `i` 个二维码的四个真实角点坐标。`points`/`bbox` 是调用方用来裁剪、叠加、做几何校正
+/// 的依据，所以这里不再在数据缺失/不合规时伪造一个居中方块—— 宁可让这个码检测失败，
+/// 也不能返回看似合法实则无意义的坐标。
+fn extract_corner_points(points: &Vector<Mat>, i: usize, _image: &Mat) -> Result<Vec<[f32; 2]>, Box<dyn std::error::Error>> {
+    if i >= points.len() {
+        return Err(format!("No corner points data returned for QR code {}", i).into());
+    }
+
+    let point_mat = points.get(i)?;
+
+    // WeChat/Classic 返回的points是每个QR码的四个角点
+    // point_mat 通常是 4x2 的矩阵（每行一个角点的 x,y）；也兼容按
+    // [x1, y1, x2, y2, x3, y3, x4, y4] 平铺的 8 个值
+    if point_mat.rows() >= 4 && point_mat.cols() >= 2 {
+        let mut coordinates = Vec::new();
+        for j in 0..4 {
+            let x = (*point_mat.at_2d::<f32>(j, 0)? * 10.0).round() / 10.0; // 保留1位小数
+            let y = (*point_mat.at_2d::<f32>(j, 1)? * 10.0).round() / 10.0; // 保留1位小数
+            coordinates.push([x, y]);
+        }
+        Ok(coordinates)
+    } else if point_mat.total() >= 8 {
+        let mut coordinates = Vec::new();
+        for j in 0..4 {
+            let x = (*point_mat.at::<f32>(j * 2)? * 10.0).round() / 10.0; // 保留1位小数
+            let y = (*point_mat.at::<f32>(j * 2 + 1)? * 10.0).round() / 10.0; // 保留1位小数
+            coordinates.push([x, y]);
+        }
+        Ok(coordinates)
+    } else {
+        Err(format!(
+            "Corner points for QR code {} have an unexpected shape ({}x{}, {} total)",
+            i, point_mat.rows(), point_mat.cols(), point_mat.total()
+        ).into())
+    }
+}
+
+fn build_qr_result(text: String, coordinates: Vec<[f32; 2]>, parse_payload: bool) -> QRCodeResult {
+    let min_x = coordinates.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min);
+    let max_x = coordinates.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max);
+    let min_y = coordinates.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min);
+    let max_y = coordinates.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max);
+
+    // 边界框坐标也保留1位小数
+    let bbox_x = (min_x * 10.0).round() / 10.0;
+    let bbox_y = (min_y * 10.0).round() / 10.0;
+    let bbox_width = ((max_x - min_x) * 10.0).round() / 10.0;
+    let bbox_height = ((max_y - min_y) * 10.0).round() / 10.0;
+
+    let payload = if parse_payload {
+        Some(payload::parse_payload(&text))
+    } else {
+        None
+    };
+
+    QRCodeResult {
+        text,
+        points: coordinates,
+        bbox: BoundingBox {
+            x: bbox_x,
+            y: bbox_y,
+            width: bbox_width,
+            height: bbox_height,
+        },
+        payload,
+    }
+}
+
+// 单次检测允许的最长耗时，防止畸形输入把 ZXing/WeChat 解码器卡死（可通过 DETECTION_TIMEOUT_MS 覆盖）
+fn detection_timeout() -> std::time::Duration {
+    let ms = std::env::var("DETECTION_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(10_000);
+    std::time::Duration::from_millis(ms)
+}
+
+// 图片像素预算，防止畸形/超大图片拖垮检测器（可通过 MAX_IMAGE_PIXELS 覆盖）
+fn max_image_pixels() -> u64 {
+    std::env::var("MAX_IMAGE_PIXELS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(50_000_000)
+}
+
+/// 在检测前做基础合法性校验：像素预算、通道数、尺寸是否荒谬
+fn validate_decoded_image(image: &Mat) -> Result<(), String> {
+    let size = image.size().map_err(|e| format!("Failed to read image size: {}", e))?;
+    let channels = image.channels();
+
+    if size.width <= 0 || size.height <= 0 {
+        return Err(format!("Invalid image dimensions: {}x{}", size.width, size.height));
+    }
+    if channels == 0 {
+        return Err("Decoded image has zero channels".to_string());
+    }
+
+    let pixels = size.width as u64 * size.height as u64;
+    let budget = max_image_pixels();
+    if pixels > budget {
+        return Err(format!(
+            "Image has {} pixels, exceeding the configured budget of {} (MAX_IMAGE_PIXELS)",
+            pixels, budget
+        ));
+    }
+
+    Ok(())
+}
+
+/// 在一个捕获 panic 的边界内运行检测：若 WeChat 检测器 panic（已知会在畸形输入上发生），
+/// 丢弃这个可能已损坏的池化实例而不是把它归还池子，从池里换一个实例（池空时现建一个），
+/// 本次检测不重试，直接返回错误。
+///
+/// 返回的 `Reusable<QRCodeDetector>` 要么是原来那个（检测成功/正常失败时），要么是 panic
+/// 后重新从池里拉出来的替换实例 —— 调用方（尤其是复用同一个租约的 WebSocket 连接）应该
+/// 始终保留这个返回值继续使用，而不是沿用已经可能损坏的旧引用。
+fn detect_with_panic_guard(
+    mut detector: Reusable<QRCodeDetector>,
+    image: &Mat,
+    parse_payload: bool,
+) -> (Reusable<QRCodeDetector>, Result<Vec<QRCodeResult>, String>) {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        detector.detect_qr_codes(image, parse_payload)
+    }));
+
+    match result {
+        Ok(detection_result) => (
+            detector,
+            detection_result.map_err(|e| format!("QRCode detection failed: {}", e)),
+        ),
+        Err(panic_payload) => {
+            let panic_message = panic_payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic_payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+
+            error!("WeChat QRCode detector panicked, discarding pooled instance: {}", panic_message);
+
+            // 将可能已损坏的实例从池中分离并直接丢弃，而不是通过 Drop 归还
+            let (pool, _poisoned) = Reusable::detach(detector);
+            let replacement = pool.pull(|| match QRCodeDetector::new() {
+                Ok(fresh) => fresh,
+                Err(e) => {
+                    error!("Failed to rebuild QRCode detector after panic: {}", e);
+                    panic!("Cannot create fallback QRCode detector");
                 }
-            } else {
-                // 如果没有角点数据，使用备用方案
-                warn!("No corner points data for QR code {}, using fallback", i);
-                let img_width = image.cols() as f32;
-                let img_height = image.rows() as f32;
-                
-                let size = (img_width.min(img_height) * 0.6) as f32;
-                let x_offset = (img_width - size) / 2.0;
-                let y_offset = (img_height - size) / 2.0;
-                
-                let coordinates = vec![
-                    [x_offset, y_offset],
-                    [x_offset + size, y_offset],
-                    [x_offset + size, y_offset + size],
-                    [x_offset, y_offset + size],
-                ];
-                
-                let min_x = coordinates.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min);
-                let max_x = coordinates.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max);
-                let min_y = coordinates.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min);
-                let max_y = coordinates.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max);
-                
-                results.push(QRCodeResult {
-                    text,
-                    points: coordinates,
-                    bbox: BoundingBox {
-                        x: min_x,
-                        y: min_y,
-                        width: max_x - min_x,
-                        height: max_y - min_y,
-                    },
-                });
-            }
+            });
+
+            (
+                replacement,
+                Err(format!("QRCode detection panicked on malformed input: {}", panic_message)),
+            )
         }
-        
-        Ok(results)
     }
 }
 
@@ -276,22 +514,15 @@ fn get_detector_pool() -> &'static DetectorPool {
     }
 }
 
-// 检查模型文件是否存在的函数
+// 报告启动时实际会用哪个检测后端；WeChat 模型缺失时 `effective_backend_kind` 已经自动
+// 降级到 `rqrr`，所以这里不再需要像过去那样在模型缺失时让启动失败
 fn check_model_files() -> Result<(), String> {
-    let model_files = [
-        "models/detect.prototxt",
-        "models/detect.caffemodel", 
-        "models/sr.prototxt",
-        "models/sr.caffemodel"
-    ];
-    
-    for model_file in &model_files {
-        if !PathBuf::from(model_file).exists() {
-            return Err(format!("WeChat QRCode model file not found: {}", model_file));
-        }
+    match effective_backend_kind() {
+        DetectorBackendKind::Classic => info!("DETECTOR_BACKEND=classic, skipping WeChat model file check"),
+        DetectorBackendKind::PureRust => info!("Using the pure-Rust rqrr backend, no model files required"),
+        DetectorBackendKind::WeChat => info!("All WeChat QRCode model files found"),
     }
-    
-    info!("All WeChat QRCode model files found");
+
     Ok(())
 }
 
@@ -336,9 +567,18 @@ async fn serve_static_files() -> Result<Response, StatusCode> {
     Err(StatusCode::NOT_FOUND)
 }
 
-async fn detect_from_file(mut multipart: Multipart) -> Result<Json<DetectionResponse>, StatusCode> {
+#[derive(Debug, Deserialize)]
+struct DetectQuery {
+    #[serde(default)]
+    parse: bool,
+}
+
+async fn detect_from_file(
+    Query(query): Query<DetectQuery>,
+    mut multipart: Multipart,
+) -> Result<Json<DetectionResponse>, StatusCode> {
     let start_time = Instant::now();
-    
+
     while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
         let name = field.name().unwrap_or("");
         
@@ -376,42 +616,80 @@ async fn detect_from_file(mut multipart: Multipart) -> Result<Json<DetectionResp
                         image_width: 0,
                         image_height: 0,
                         pool_acquisition_time_ms: 0.0,
+                        backend: effective_backend_kind().label().to_string(),
                     },
+                    reassembled: None,
                 }));
             }
             
             let image_size = image.size().unwrap();
             info!("Image decoded successfully ({}x{}), detecting QRCodes...", image_size.width, image_size.height);
-            
-            // 从对象池获取检测器
-            let pool_start = Instant::now();
-            let pool = get_detector_pool();
-            let mut detector = pool.pull(|| {
-                match QRCodeDetector::new() {
-                    Ok(detector) => detector,
-                    Err(e) => {
-                        error!("Failed to create fallback QRCode detector: {}", e);
-                        panic!("Cannot create fallback QRCode detector");
+
+            validate_decoded_image(&image).map_err(|e| {
+                warn!("Rejecting image before detection: {}", e);
+                StatusCode::BAD_REQUEST
+            })?;
+
+            // 先拿一个信号量许可，把同时在跑的检测数量限制在池容量以内；后端已经饱和时
+            // 与其排队等到天荒地老，不如在超时后直接报 503 让调用方退避重试
+            let _permit = tokio::time::timeout(detection_timeout(), detection_semaphore().acquire())
+                .await
+                .map_err(|_| {
+                    warn!("Timed out waiting for a free detector slot");
+                    StatusCode::SERVICE_UNAVAILABLE
+                })?
+                .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+
+            // 从对象池租检测器 + 实际检测都是同步的 OpenCV 调用，丢进 spawn_blocking
+            // 避免占住 tokio 的异步工作线程；再包一层超时，畸形图片卡住解码器时报 408
+            // 而不是让请求无限挂起（spawn_blocking 里的线程本身无法被强行打断，只是不再等它）
+            let parse_payload = query.parse;
+            let detection_task = tokio::task::spawn_blocking(move || {
+                let pool_start = Instant::now();
+                let pool = get_detector_pool();
+                let detector = pool.pull(|| {
+                    match QRCodeDetector::new() {
+                        Ok(detector) => detector,
+                        Err(e) => {
+                            error!("Failed to create fallback QRCode detector: {}", e);
+                            panic!("Cannot create fallback QRCode detector");
+                        }
                     }
-                }
+                });
+                let pool_acquisition_time = pool_start.elapsed().as_secs_f64() * 1000.0;
+
+                let detection_start = Instant::now();
+                let (_detector, detection_result) = detect_with_panic_guard(detector, &image, parse_payload);
+                let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
+
+                (pool_acquisition_time, detection_time, detection_result)
             });
-            let pool_acquisition_time = pool_start.elapsed().as_secs_f64() * 1000.0;
-            
-            // 二维码检测时间统计
-            let detection_start = Instant::now();
-            let qrcodes = detector.detect_qr_codes(&image).map_err(|e| {
+
+            let (pool_acquisition_time, detection_time, detection_result) =
+                match tokio::time::timeout(detection_timeout(), detection_task).await {
+                    Ok(join_result) => join_result.map_err(|e| {
+                        error!("Detection task panicked: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?,
+                    Err(_) => {
+                        warn!("Detection timed out after {:?}", detection_timeout());
+                        return Err(StatusCode::REQUEST_TIMEOUT);
+                    }
+                };
+
+            // 检测器会自动归还到池中（通过Drop trait），除非在 panic 后被替换
+
+            let qrcodes = detection_result.map_err(|e| {
                 error!("QRCode detection failed: {}", e);
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
-            let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
-            
-            // 检测器会自动归还到池中（通过Drop trait）
-            
+
             let total_time = start_time.elapsed().as_secs_f64() * 1000.0;
             
-            info!("Detected {} QR codes in uploaded image (decode: {:.2}ms, pool: {:.2}ms, detection: {:.2}ms, total: {:.2}ms)", 
+            info!("Detected {} QR codes in uploaded image (decode: {:.2}ms, pool: {:.2}ms, detection: {:.2}ms, total: {:.2}ms)",
                   qrcodes.len(), decode_time, pool_acquisition_time, detection_time, total_time);
-            
+
+            let reassembled = reassemble_qrcodes(&qrcodes);
             return Ok(Json(DetectionResponse {
                 success: true,
                 message: format!("Detected {} QR code(s)", qrcodes.len()),
@@ -424,7 +702,9 @@ async fn detect_from_file(mut multipart: Multipart) -> Result<Json<DetectionResp
                     image_width: image_size.width,
                     image_height: image_size.height,
                     pool_acquisition_time_ms: pool_acquisition_time,
+                    backend: effective_backend_kind().label().to_string(),
                 },
+                reassembled,
             }));
         }
     }
@@ -433,6 +713,7 @@ async fn detect_from_file(mut multipart: Multipart) -> Result<Json<DetectionResp
 }
 
 async fn detect_from_base64(
+    Query(query): Query<DetectQuery>,
     Json(payload): Json<HashMap<String, String>>,
 ) -> Result<Json<DetectionResponse>, StatusCode> {
     let start_time = Instant::now();
@@ -475,40 +756,78 @@ async fn detect_from_base64(
                 image_width: 0,
                 image_height: 0,
                 pool_acquisition_time_ms: 0.0,
+                backend: effective_backend_kind().label().to_string(),
             },
+            reassembled: None,
         }));
     }
     
     let image_size = image.size().unwrap();
     info!("Base64 image decoded successfully ({}x{}), detecting QRCodes...", image_size.width, image_size.height);
-    
-    // 从对象池获取检测器
-    let pool_start = Instant::now();
-    let pool = get_detector_pool();
-    let mut detector = pool.pull(|| {
-        match QRCodeDetector::new() {
-            Ok(detector) => detector,
-            Err(e) => {
-                error!("Failed to create fallback QRCode detector: {}", e);
-                panic!("Cannot create fallback QRCode detector");
+
+    validate_decoded_image(&image).map_err(|e| {
+        warn!("Rejecting base64 image before detection: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    // 先拿一个信号量许可，把同时在跑的检测数量限制在池容量以内；后端已经饱和时
+    // 与其排队等到天荒地老，不如在超时后直接报 503 让调用方退避重试
+    let _permit = tokio::time::timeout(detection_timeout(), detection_semaphore().acquire())
+        .await
+        .map_err(|_| {
+            warn!("Timed out waiting for a free detector slot");
+            StatusCode::SERVICE_UNAVAILABLE
+        })?
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+
+    // 从对象池租检测器 + 实际检测都是同步的 OpenCV 调用，丢进 spawn_blocking
+    // 避免占住 tokio 的异步工作线程；再包一层超时，畸形图片卡住解码器时报 408
+    // 而不是让请求无限挂起（spawn_blocking 里的线程本身无法被强行打断，只是不再等它）
+    let parse_payload = query.parse;
+    let detection_task = tokio::task::spawn_blocking(move || {
+        let pool_start = Instant::now();
+        let pool = get_detector_pool();
+        let detector = pool.pull(|| {
+            match QRCodeDetector::new() {
+                Ok(detector) => detector,
+                Err(e) => {
+                    error!("Failed to create fallback QRCode detector: {}", e);
+                    panic!("Cannot create fallback QRCode detector");
+                }
             }
-        }
+        });
+        let pool_acquisition_time = pool_start.elapsed().as_secs_f64() * 1000.0;
+
+        let detection_start = Instant::now();
+        let (_detector, detection_result) = detect_with_panic_guard(detector, &image, parse_payload);
+        let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
+
+        (pool_acquisition_time, detection_time, detection_result)
     });
-    let pool_acquisition_time = pool_start.elapsed().as_secs_f64() * 1000.0;
-    
-    // 二维码检测时间统计
-    let detection_start = Instant::now();
-    let qrcodes = detector.detect_qr_codes(&image).map_err(|e| {
+
+    let (pool_acquisition_time, detection_time, detection_result) =
+        match tokio::time::timeout(detection_timeout(), detection_task).await {
+            Ok(join_result) => join_result.map_err(|e| {
+                error!("Detection task panicked: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+            Err(_) => {
+                warn!("Detection timed out after {:?}", detection_timeout());
+                return Err(StatusCode::REQUEST_TIMEOUT);
+            }
+        };
+
+    let qrcodes = detection_result.map_err(|e| {
         error!("QRCode detection failed: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
-    let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
-    
+
     let total_time = start_time.elapsed().as_secs_f64() * 1000.0;
     
-    info!("Detected {} QR codes in base64 image (decode: {:.2}ms, pool: {:.2}ms, detection: {:.2}ms, total: {:.2}ms)", 
+    info!("Detected {} QR codes in base64 image (decode: {:.2}ms, pool: {:.2}ms, detection: {:.2}ms, total: {:.2}ms)",
           qrcodes.len(), decode_time, pool_acquisition_time, detection_time, total_time);
-    
+
+    let reassembled = reassemble_qrcodes(&qrcodes);
     Ok(Json(DetectionResponse {
         success: true,
         message: format!("Detected {} QR code(s)", qrcodes.len()),
@@ -521,103 +840,612 @@ async fn detect_from_base64(
             image_width: image_size.width,
             image_height: image_size.height,
             pool_acquisition_time_ms: pool_acquisition_time,
+            backend: effective_backend_kind().label().to_string(),
         },
+        reassembled,
     }))
 }
 
-// WebSocket 升级处理函数
-async fn websocket_handler(ws: WebSocketUpgrade) -> Response {
-    ws.on_upgrade(handle_websocket)
+fn empty_detection_response() -> DetectionResponse {
+    DetectionResponse {
+        success: false,
+        message: "Detection failed".to_string(),
+        qrcodes: Vec::new(),
+        count: 0,
+        statistics: DetectionStatistics {
+            image_decode_time_ms: 0.0,
+            detection_time_ms: 0.0,
+            total_time_ms: 0.0,
+            image_width: 0,
+            image_height: 0,
+            pool_acquisition_time_ms: 0.0,
+            backend: effective_backend_kind().label().to_string(),
+        },
+        reassembled: None,
+    }
 }
 
-// WebSocket 连接处理
-async fn handle_websocket(socket: WebSocket) {
-    let (mut sender, mut receiver) = socket.split();
-    
-    info!("New WebSocket connection established");
-    
-    // 发送连接确认消息
-    let welcome_msg = WebSocketResponse {
-        msg_type: "connected".to_string(),
+// 限制同时进行中的检测数量为池的最大容量，避免批量请求压垮 DNN 后端
+fn detection_semaphore() -> &'static tokio::sync::Semaphore {
+    static SEMAPHORE: std::sync::OnceLock<tokio::sync::Semaphore> = std::sync::OnceLock::new();
+    SEMAPHORE.get_or_init(|| {
+        let (_initial_size, max_size) = get_pool_config();
+        tokio::sync::Semaphore::new(max_size)
+    })
+}
+
+fn detect_one_base64(base64_data: String, parse_payload: bool) -> DetectionResponse {
+    let pool = get_detector_pool();
+    let detector = pool.pull(|| {
+        QRCodeDetector::new().unwrap_or_else(|e| {
+            error!("Failed to create fallback QRCode detector: {}", e);
+            panic!("Cannot create fallback QRCode detector");
+        })
+    });
+
+    let (_detector, result) = detect_qr_from_base64_leased(base64_data, parse_payload, detector);
+    result.unwrap_or_else(|e| {
+        warn!("Batch item failed: {}", e);
+        empty_detection_response()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchDetectRequest {
+    images: Vec<String>,
+    #[serde(default)]
+    parse: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchDetectResponse {
+    success: bool,
+    message: String,
+    results: Vec<DetectionResponse>,
+    total_time_ms: f64,
+    // 跨整个批次（而不只是单张图片内部）重组 Structured Append 序列：同一次分片出的码
+    // 可能被拆分到不同的图片里分别上传。只有经由本服务 `/encode`（mode=STRUCTURED_APPEND）
+    // 生成的码才带得到重组所需的头部，见 `structured_append` 模块顶部的说明——第三方生成
+    // 的 Structured Append 二维码不带这个头，这里读不出来，自然重组不了。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reassembled: Option<String>,
+}
+
+// 批次大小上限（可通过 MAX_BATCH_SIZE 覆盖）：信号量只在每个任务真正触达解码步骤时才生效，
+// 拦不住在那之前就被无限制 tokio::spawn 出来的任务本身，所以这里先按请求体里的图片数量
+// 拒绝掉不合理的批次，而不是任由 `images` 有多大就 spawn 多少个任务
+fn max_batch_size() -> usize {
+    std::env::var("MAX_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(64)
+}
+
+// 批量检测：每张图片各自从池里租一个检测器，在 spawn_blocking 里并发跑，
+// 但受信号量限制同时在跑的数量，使吞吐量跟随池子大小而不是无限制地压垮 DNN 后端
+async fn detect_batch_handler(
+    Query(query): Query<DetectQuery>,
+    Json(payload): Json<BatchDetectRequest>,
+) -> Result<Json<BatchDetectResponse>, StatusCode> {
+    let start_time = Instant::now();
+    let parse_payload = payload.parse || query.parse;
+
+    let max_batch_size = max_batch_size();
+    if payload.images.len() > max_batch_size {
+        warn!(
+            "Rejecting batch detect request with {} images (limit is {})",
+            payload.images.len(),
+            max_batch_size
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let tasks: Vec<_> = payload
+        .images
+        .into_iter()
+        .map(|base64_data| {
+            tokio::spawn(async move {
+                let _permit = detection_semaphore()
+                    .acquire()
+                    .await
+                    .expect("detection semaphore should never be closed");
+                tokio::task::spawn_blocking(move || detect_one_base64(base64_data, parse_payload))
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("Batch detection task panicked: {}", e);
+                        empty_detection_response()
+                    })
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.map_err(|e| {
+            error!("Batch detection task join error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?);
+    }
+
+    // 分片可能被拆分到了批次里的不同图片中，所以在这里把所有图片的解码文本拉平到一起
+    // 再重组一次，而不是只依赖每张图片各自在 DetectionResponse.reassembled 里的结果
+    let all_texts: Vec<String> = results.iter().flat_map(|r| r.qrcodes.iter().map(|q| q.text.clone())).collect();
+    let reassembled = reassemble_texts(&all_texts);
+
+    Ok(Json(BatchDetectResponse {
         success: true,
-        message: "WebSocket connected successfully".to_string(),
-        qrcodes: None,
-        count: None,
-        statistics: None,
-        error: None,
-    };
-    
-    if let Ok(welcome_json) = serde_json::to_string(&welcome_msg) {
-        if sender.send(Message::Text(welcome_json)).await.is_err() {
-            error!("Failed to send welcome message");
-            return;
+        message: format!("Processed {} image(s)", results.len()),
+        total_time_ms: start_time.elapsed().as_secs_f64() * 1000.0,
+        results,
+        reassembled,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct GenerateQuery {
+    #[serde(default)]
+    raw: bool,
+}
+
+// 生成二维码：默认返回 JSON（base64），?raw=true 时直接返回图片字节
+async fn generate_handler(
+    Query(query): Query<GenerateQuery>,
+    Json(payload): Json<GenerateRequest>,
+) -> Result<Response, StatusCode> {
+    if query.raw {
+        let rendered = generate::render(&payload).map_err(|e| {
+            error!("QR generation failed: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+        return Ok(Response::builder()
+            .header("Content-Type", rendered.content_type)
+            .body(axum::body::Body::from(rendered.bytes))
+            .unwrap());
+    }
+
+    match generate_qr(&payload) {
+        Ok(result) => Ok(Json(result).into_response()),
+        Err(e) => {
+            warn!("QR generation failed: {}", e);
+            Err(StatusCode::BAD_REQUEST)
         }
     }
-    
-    // 处理接收到的消息
-    while let Some(msg) = receiver.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                debug!("Received WebSocket text message: {}", text);
-                
-                // 解析请求
-                match serde_json::from_str::<WebSocketRequest>(&text) {
-                    Ok(request) => {
-                        let response = handle_websocket_request(request).await;
-                        
-                        if let Ok(response_json) = serde_json::to_string(&response) {
-                            if sender.send(Message::Text(response_json)).await.is_err() {
-                                error!("Failed to send WebSocket response");
-                                break;
-                            }
-                        }
-                        
-                        // 如果是关闭请求，结束连接
-                        if response.msg_type == "close" {
-                            info!("WebSocket connection closed by client request");
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to parse WebSocket request: {}", e);
-                        let error_response = WebSocketResponse {
-                            msg_type: "error".to_string(),
-                            success: false,
-                            message: "Invalid request format".to_string(),
-                            qrcodes: None,
-                            count: None,
-                            statistics: None,
-                            error: Some(format!("Parse error: {}", e)),
-                        };
-                        
-                        if let Ok(error_json) = serde_json::to_string(&error_response) {
-                            if sender.send(Message::Text(error_json)).await.is_err() {
-                                break;
-                            }
-                        }
-                    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EncodeQuery {
+    #[serde(default)]
+    raw: bool,
+}
+
+// 走 OpenCV QRCodeEncoder 的编码路径：支持选择编码模式/纠错等级/版本号，
+// STRUCTURED_APPEND 模式下 ?raw=true 没有单张图可返回，直接报 400
+async fn encode_handler(
+    Query(query): Query<EncodeQuery>,
+    Json(payload): Json<EncodeRequest>,
+) -> Result<Response, StatusCode> {
+    if query.raw {
+        if payload.mode == qrencode::EncodeMode::StructuredAppend {
+            warn!("QR encode with ?raw=true does not support STRUCTURED_APPEND (multiple images)");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let rendered = qrencode::render_png(&payload).map_err(|e| {
+            error!("QR encode failed: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+        return Ok(Response::builder()
+            .header("Content-Type", rendered.content_type)
+            .body(axum::body::Body::from(rendered.bytes))
+            .unwrap());
+    }
+
+    match encode_qr(&payload) {
+        Ok(result) => Ok(Json(result).into_response()),
+        Err(e) => {
+            warn!("QR encode failed: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CreateSessionResponse {
+    token: String,
+    state: session::SessionState,
+    image_base64: String,
+}
+
+// 扫码登录：生成随机 token + 一个指向它的二维码，客户端轮询 /session/{token} 等待状态推进
+async fn create_session_handler() -> Result<Json<CreateSessionResponse>, StatusCode> {
+    let token = session::create();
+    let deep_link = format!("qrlogin://scan?token={}", token);
+
+    let request = GenerateRequest {
+        text: deep_link,
+        error_correction: Default::default(),
+        size: 256,
+        margin: 4,
+        format: "base64".to_string(),
+    };
+
+    let rendered = generate::render_png(&request).map_err(|e| {
+        error!("Failed to render session QR code: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(CreateSessionResponse {
+        token,
+        state: session::SessionState::NotScanned,
+        image_base64: BASE64_STANDARD.encode(&rendered.bytes),
+    }))
+}
+
+async fn session_status_handler(Path(token): Path<String>) -> Result<Json<session::SessionStatus>, StatusCode> {
+    session::status(&token).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+// 扫码端上报已扫描：NotScanned -> Scanned
+async fn session_scan_handler(Path(token): Path<String>) -> Result<Json<session::SessionStatus>, StatusCode> {
+    session::mark_scanned(&token).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+// 扫码端确认登录：Scanned -> Confirmed
+async fn session_confirm_handler(Path(token): Path<String>) -> Result<Json<session::SessionStatus>, StatusCode> {
+    session::confirm(&token).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+fn default_scan_device() -> String {
+    "/dev/video0".to_string()
+}
+
+fn default_scan_timeout_ms() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanCameraQuery {
+    #[serde(default = "default_scan_device")]
+    device: String,
+    #[serde(default = "default_scan_timeout_ms")]
+    timeout_ms: u64,
+}
+
+/// 从本机 V4L2 摄像头抓帧并持续检测，直到解出第一个二维码或超时
+async fn scan_camera_handler(
+    Query(query): Query<ScanCameraQuery>,
+) -> Result<Json<DetectionResponse>, StatusCode> {
+    let device = query.device.clone();
+    let timeout = std::time::Duration::from_millis(query.timeout_ms);
+
+    // 跟其它检测入口一样，先拿一个信号量许可再进池子，防止并发的摄像头扫描请求
+    // 各自无限制地占着检测器实例，把池子撑爆
+    let _permit = tokio::time::timeout(detection_timeout(), detection_semaphore().acquire())
+        .await
+        .map_err(|_| {
+            warn!("Timed out waiting for a free detector slot for camera scan");
+            StatusCode::SERVICE_UNAVAILABLE
+        })?
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let start_time = Instant::now();
+
+        scan::scan_until(&device, timeout, |frame_bytes| {
+            let mat = Mat::from_slice(&frame_bytes).ok()?;
+            let image = opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR).ok()?;
+            if image.empty() || validate_decoded_image(&image).is_err() {
+                return None;
+            }
+
+            let pool = get_detector_pool();
+            let detector = pool.pull(|| {
+                QRCodeDetector::new().unwrap_or_else(|e| {
+                    error!("Failed to create fallback QRCode detector: {}", e);
+                    panic!("Cannot create fallback QRCode detector");
+                })
+            });
+
+            let (_detector, detection_result) = detect_with_panic_guard(detector, &image, false);
+            let qrcodes = detection_result.ok()?;
+            if qrcodes.is_empty() {
+                return None;
+            }
+
+            let image_size = image.size().ok()?;
+            let reassembled = reassemble_qrcodes(&qrcodes);
+            Some(DetectionResponse {
+                success: true,
+                message: format!("Detected {} QR code(s) from camera", qrcodes.len()),
+                count: qrcodes.len(),
+                qrcodes,
+                statistics: DetectionStatistics {
+                    image_decode_time_ms: 0.0,
+                    detection_time_ms: 0.0,
+                    total_time_ms: start_time.elapsed().as_secs_f64() * 1000.0,
+                    image_width: image_size.width,
+                    image_height: image_size.height,
+                    pool_acquisition_time_ms: 0.0,
+                    backend: effective_backend_kind().label().to_string(),
+                },
+                reassembled,
+            })
+        })
+    })
+    .await
+    .map_err(|e| {
+        error!("Camera scan task panicked: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match result {
+        Ok(response) => Ok(Json(response)),
+        Err(scan::CameraError::Timeout { .. }) => Err(StatusCode::REQUEST_TIMEOUT),
+        Err(e) => {
+            warn!("Camera scan failed: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+fn default_scan_stream_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanStreamQuery {
+    #[serde(default = "default_scan_device")]
+    device: String,
+    #[serde(default = "default_scan_stream_timeout_secs")]
+    timeout: u64,
+}
+
+/// SSE 长轮询扫描：跟 `/scan/camera` 不同，这里不是解出第一个码就返回，而是持续抓帧、
+/// 每一帧解出二维码就推送一个 event（同一个码在连续帧里会重复推送，不做去重），
+/// 直到超时或客户端断开连接
+async fn scan_stream_handler(
+    Query(query): Query<ScanStreamQuery>,
+) -> Sse<impl futures_util::Stream<Item = Result<Event, std::convert::Infallible>>> {
+    let device = query.device.clone();
+    let timeout = std::time::Duration::from_secs(query.timeout);
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Vec<QRCodeResult>>(16);
+
+    tokio::task::spawn_blocking(move || {
+        let start = Instant::now();
+
+        // 跟其它检测入口一样受信号量约束，但这里一拿就是整个扫描周期（最长 `timeout`），
+        // 不值得排队等待——没有空闲槽位就直接放弃，而不是占着请求等到天荒地老
+        let _permit = match detection_semaphore().try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                warn!("No free detector slot available for SSE scan; rejecting");
+                return;
+            }
+        };
+
+        let (dev, device_name) = match scan::open(&device) {
+            Ok(opened) => opened,
+            Err(e) => {
+                warn!("Failed to open camera for SSE scan: {}", e);
+                return;
+            }
+        };
+        let mut source = match scan::CameraFrameSource::from_device(&dev, &device_name) {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Failed to open camera for SSE scan: {}", e);
+                return;
+            }
+        };
+
+        while start.elapsed() < timeout {
+            let frame_bytes = match source.grab_frame() {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!("Camera frame capture failed during SSE scan: {}", e);
+                    break;
                 }
+            };
+
+            let mat = match Mat::from_slice(&frame_bytes) {
+                Ok(mat) => mat,
+                Err(_) => continue,
+            };
+            let image = match opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR) {
+                Ok(image) => image,
+                Err(_) => continue,
+            };
+            if image.empty() || validate_decoded_image(&image).is_err() {
+                continue;
             }
-            Ok(Message::Binary(data)) => {
-                debug!("Received WebSocket binary message: {} bytes", data.len());
-                
-                // 处理二进制图片数据
-                let response = handle_websocket_binary_request(data).await;
-                
-                if let Ok(response_json) = serde_json::to_string(&response) {
-                    if sender.send(Message::Text(response_json)).await.is_err() {
-                        error!("Failed to send WebSocket binary response");
+
+            let pool = get_detector_pool();
+            let detector = pool.pull(|| {
+                QRCodeDetector::new().unwrap_or_else(|e| {
+                    error!("Failed to create fallback QRCode detector: {}", e);
+                    panic!("Cannot create fallback QRCode detector");
+                })
+            });
+
+            let (_detector, detection_result) = detect_with_panic_guard(detector, &image, false);
+            if let Ok(qrcodes) = detection_result {
+                if !qrcodes.is_empty() && tx.blocking_send(qrcodes).is_err() {
+                    // 客户端已经断开连接，没必要继续抓帧
+                    break;
+                }
+            }
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|qrcodes| {
+            let event = Event::default()
+                .json_data(&qrcodes)
+                .unwrap_or_else(|_| Event::default().data("[]"));
+            (Ok::<_, std::convert::Infallible>(event), rx)
+        })
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// WebSocket 升级处理函数
+async fn websocket_handler(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(handle_websocket)
+}
+
+/// 一个待处理的帧：文本 "detect"/"generate" 请求或二进制图片数据。
+/// 用 `watch` 通道承载——新值到达时会直接覆盖旧值，天然实现“只保留最新一帧”的丢帧背压。
+enum PendingFrame {
+    Request(WebSocketRequest),
+    Binary(Vec<u8>),
+}
+
+// WebSocket 连接处理：为整个连接租用一个检测器并在所有帧之间复用，
+// 同时用丢帧背压保证慢检测器不会在高帧率客户端下堆积无界队列。
+async fn handle_websocket(socket: WebSocket) {
+    let (sender, mut receiver) = socket.split();
+    let sender = std::sync::Arc::new(tokio::sync::Mutex::new(sender));
+
+    info!("New WebSocket connection established");
+
+    let welcome_msg = WebSocketResponse {
+        msg_type: "connected".to_string(),
+        success: true,
+        message: "WebSocket connected successfully".to_string(),
+        qrcodes: None,
+        count: None,
+        statistics: None,
+        error: None,
+        image_base64: None,
+        results: None,
+    };
+
+    if let Ok(welcome_json) = serde_json::to_string(&welcome_msg) {
+        if sender.lock().await.send(Message::Text(welcome_json)).await.is_err() {
+            error!("Failed to send welcome message");
+            return;
+        }
+    }
+
+    // 这条连接要把检测器租到底（见下面），不值得排队等待空闲槽位——
+    // 跟 SSE 扫描一样，没有空闲槽位就直接拒绝这条连接
+    let permit = match detection_semaphore().try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            warn!("No free detector slot available for WebSocket connection; rejecting");
+            let error_msg = WebSocketResponse {
+                msg_type: "error".to_string(),
+                success: false,
+                message: "Server is at capacity, please retry later".to_string(),
+                qrcodes: None,
+                count: None,
+                statistics: None,
+                error: Some("no free detector slot".to_string()),
+                image_base64: None,
+                results: None,
+            };
+            if let Ok(error_json) = serde_json::to_string(&error_msg) {
+                let _ = sender.lock().await.send(Message::Text(error_json)).await;
+            }
+            return;
+        }
+    };
+
+    // 为这个连接租用一个检测器，整条连接生命周期内复用，避免每帧都 pool.pull()
+    let pool = get_detector_pool();
+    let leased_detector = pool.pull(|| {
+        QRCodeDetector::new().unwrap_or_else(|e| {
+            error!("Failed to create fallback QRCode detector: {}", e);
+            panic!("Cannot create fallback QRCode detector");
+        })
+    });
+
+    let (frame_tx, mut frame_rx) = tokio::sync::watch::channel::<Option<PendingFrame>>(None);
+
+    // 处理任务独占检测器租约：每次只处理 watch 里的最新一帧，处理期间到达的新帧
+    // 会直接替换掉旧的，旧帧被悄悄丢弃，绝不会排队等待。信号量许可跟着这个任务
+    // 一起移动，连接/任务结束时随之释放。
+    let processing_sender = sender.clone();
+    let processing_task = tokio::spawn(async move {
+        let _permit = permit;
+        let mut detector = leased_detector;
+
+        while frame_rx.changed().await.is_ok() {
+            let frame = frame_rx.borrow_and_update().take();
+            let Some(frame) = frame else { continue };
+
+            let response = match frame {
+                PendingFrame::Request(request) => {
+                    let (next_detector, response) = handle_websocket_request(request, detector);
+                    detector = next_detector;
+                    response
+                }
+                PendingFrame::Binary(data) => {
+                    let (next_detector, response) = handle_websocket_binary_request(data, detector);
+                    detector = next_detector;
+                    response
+                }
+            };
+
+            if let Ok(response_json) = serde_json::to_string(&response) {
+                if processing_sender.lock().await.send(Message::Text(response_json)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    // 主循环只负责读取/分发：解析出来的新帧立刻投进 watch 通道，不等待上一帧处理完，
+    // ping/close 等控制帧则直接处理，保证长时间检测期间连接依然响应。
+    while let Some(msg) = receiver.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                debug!("Received WebSocket text message: {}", text);
+
+                match serde_json::from_str::<WebSocketRequest>(&text) {
+                    Ok(request) if request.msg_type == "close" => {
+                        info!("WebSocket connection closed by client request");
+                        let _ = sender.lock().await.send(Message::Close(None)).await;
                         break;
                     }
+                    Ok(request) => {
+                        let _ = frame_tx.send(Some(PendingFrame::Request(request)));
+                    }
+                    Err(e) => {
+                        error!("Failed to parse WebSocket request: {}", e);
+                        let error_response = WebSocketResponse {
+                            msg_type: "error".to_string(),
+                            success: false,
+                            message: "Invalid request format".to_string(),
+                            qrcodes: None,
+                            count: None,
+                            statistics: None,
+                            error: Some(format!("Parse error: {}", e)),
+                            image_base64: None,
+                            results: None,
+                        };
+
+                        if let Ok(error_json) = serde_json::to_string(&error_response) {
+                            if sender.lock().await.send(Message::Text(error_json)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
                 }
             }
-            Ok(Message::Close(_)) => {
+            Ok(Message::Binary(data)) => {
+                debug!("Received WebSocket binary message: {} bytes", data.len());
+                let _ = frame_tx.send(Some(PendingFrame::Binary(data)));
+            }
+            Ok(Message::Close(frame)) => {
                 info!("WebSocket connection closed by client");
+                let _ = sender.lock().await.send(Message::Close(frame)).await;
                 break;
             }
             Ok(Message::Ping(data)) => {
                 debug!("Received WebSocket ping");
-                if sender.send(Message::Pong(data)).await.is_err() {
+                if sender.lock().await.send(Message::Pong(data)).await.is_err() {
                     break;
                 }
             }
@@ -630,17 +1458,24 @@ async fn handle_websocket(socket: WebSocket) {
             }
         }
     }
-    
+
+    // drop(frame_tx) 发生在函数结束时，会让处理任务的 `changed()` 返回 Err 从而自然退出；
+    // 这里额外 abort 一次，防止处理任务卡在正在进行的检测上导致任务泄漏。
+    processing_task.abort();
+
     info!("WebSocket connection terminated");
 }
 
-// 处理WebSocket文本请求
-async fn handle_websocket_request(request: WebSocketRequest) -> WebSocketResponse {
+// 处理WebSocket文本请求；复用连接租用的检测器，返回值中带回（可能被 panic 替换过的）检测器
+fn handle_websocket_request(
+    request: WebSocketRequest,
+    detector: Reusable<QRCodeDetector>,
+) -> (Reusable<QRCodeDetector>, WebSocketResponse) {
     match request.msg_type.as_str() {
         "detect" => {
             if let Some(image_data) = request.image {
-                // 使用现有的base64检测逻辑
-                match detect_qr_from_base64(image_data).await {
+                let (detector, result) = detect_qr_from_base64_leased(image_data, request.parse, detector);
+                let response = match result {
                     Ok(detection_result) => WebSocketResponse {
                         msg_type: "detection_result".to_string(),
                         success: detection_result.success,
@@ -649,6 +1484,8 @@ async fn handle_websocket_request(request: WebSocketRequest) -> WebSocketRespons
                         count: Some(detection_result.count),
                         statistics: Some(detection_result.statistics),
                         error: None,
+                        image_base64: None,
+                        results: None,
                     },
                     Err(e) => WebSocketResponse {
                         msg_type: "error".to_string(),
@@ -658,32 +1495,111 @@ async fn handle_websocket_request(request: WebSocketRequest) -> WebSocketRespons
                         count: None,
                         statistics: None,
                         error: Some(e),
-                    }
-                }
+                        image_base64: None,
+                        results: None,
+                    },
+                };
+                (detector, response)
             } else {
-                WebSocketResponse {
+                (
+                    detector,
+                    WebSocketResponse {
+                        msg_type: "error".to_string(),
+                        success: false,
+                        message: "Missing image data".to_string(),
+                        qrcodes: None,
+                        count: None,
+                        statistics: None,
+                        error: Some("No image field in request".to_string()),
+                        image_base64: None,
+                        results: None,
+                    },
+                )
+            }
+        }
+        "generate" => {
+            let Some(text) = request.text else {
+                return (
+                    detector,
+                    WebSocketResponse {
+                        msg_type: "error".to_string(),
+                        success: false,
+                        message: "Missing text to encode".to_string(),
+                        qrcodes: None,
+                        count: None,
+                        statistics: None,
+                        error: Some("No text field in request".to_string()),
+                        image_base64: None,
+                        results: None,
+                    },
+                );
+            };
+
+            let generate_request = GenerateRequest {
+                text,
+                error_correction: request.error_correction.unwrap_or_default(),
+                size: request.size.unwrap_or(256),
+                margin: request.margin.unwrap_or(4),
+                format: request.format.unwrap_or_else(|| "base64".to_string()),
+            };
+
+            let response = match generate_qr(&generate_request) {
+                Ok(result) => WebSocketResponse {
+                    msg_type: "generate_result".to_string(),
+                    success: true,
+                    message: result.message,
+                    qrcodes: None,
+                    count: None,
+                    statistics: None,
+                    error: None,
+                    image_base64: result.image_base64,
+                    results: None,
+                },
+                Err(e) => WebSocketResponse {
                     msg_type: "error".to_string(),
                     success: false,
-                    message: "Missing image data".to_string(),
+                    message: "Generation failed".to_string(),
                     qrcodes: None,
                     count: None,
                     statistics: None,
-                    error: Some("No image field in request".to_string()),
-                }
-            }
+                    error: Some(e),
+                    image_base64: None,
+                    results: None,
+                },
+            };
+            (detector, response)
         }
-        "close" => {
-            WebSocketResponse {
-                msg_type: "close".to_string(),
-                success: true,
-                message: "Connection closing".to_string(),
-                qrcodes: None,
-                count: None,
-                statistics: None,
-                error: None,
+        "detect_batch" => {
+            let images = request.images.unwrap_or_default();
+            let mut detector = detector;
+            let mut results = Vec::with_capacity(images.len());
+
+            for image_data in images {
+                let (next_detector, result) = detect_qr_from_base64_leased(image_data, request.parse, detector);
+                detector = next_detector;
+                results.push(result.unwrap_or_else(|e| {
+                    warn!("Batch item failed over WebSocket: {}", e);
+                    empty_detection_response()
+                }));
             }
+
+            (
+                detector,
+                WebSocketResponse {
+                    msg_type: "batch_result".to_string(),
+                    success: true,
+                    message: format!("Processed {} image(s)", results.len()),
+                    qrcodes: None,
+                    count: Some(results.len()),
+                    statistics: None,
+                    error: None,
+                    image_base64: None,
+                    results: Some(results),
+                },
+            )
         }
-        _ => {
+        _ => (
+            detector,
             WebSocketResponse {
                 msg_type: "error".to_string(),
                 success: false,
@@ -692,14 +1608,20 @@ async fn handle_websocket_request(request: WebSocketRequest) -> WebSocketRespons
                 count: None,
                 statistics: None,
                 error: Some("Unsupported message type".to_string()),
-            }
-        }
+                image_base64: None,
+                results: None,
+            },
+        ),
     }
 }
 
-// 处理WebSocket二进制请求
-async fn handle_websocket_binary_request(binary_data: Vec<u8>) -> WebSocketResponse {
-    match detect_qr_from_binary(binary_data).await {
+// 处理WebSocket二进制请求；同样复用并带回连接租用的检测器
+fn handle_websocket_binary_request(
+    binary_data: Vec<u8>,
+    detector: Reusable<QRCodeDetector>,
+) -> (Reusable<QRCodeDetector>, WebSocketResponse) {
+    let (detector, result) = detect_qr_from_binary_leased(binary_data, detector);
+    let response = match result {
         Ok(detection_result) => WebSocketResponse {
             msg_type: "detection_result".to_string(),
             success: detection_result.success,
@@ -708,6 +1630,8 @@ async fn handle_websocket_binary_request(binary_data: Vec<u8>) -> WebSocketRespo
             count: Some(detection_result.count),
             statistics: Some(detection_result.statistics),
             error: None,
+            image_base64: None,
+            results: None,
         },
         Err(e) => WebSocketResponse {
             msg_type: "error".to_string(),
@@ -717,165 +1641,245 @@ async fn handle_websocket_binary_request(binary_data: Vec<u8>) -> WebSocketRespo
             count: None,
             statistics: None,
             error: Some(e),
-        }
-    }
+            image_base64: None,
+            results: None,
+        },
+    };
+    (detector, response)
 }
 
-// 从base64数据检测QR码的辅助函数
-async fn detect_qr_from_base64(base64_data: String) -> Result<DetectionResponse, String> {
+// 使用调用方传入的检测器解码 base64 图片：WebSocket 连接在所有帧之间复用同一个租约
+// （见 `handle_websocket`），HTTP 批量接口则每张图片各租一个（见 `detect_one_base64`）。
+// 返回更新后的检测器，panic 后会是一个替换实例。
+fn detect_qr_from_base64_leased(
+    base64_data: String,
+    parse_payload: bool,
+    detector: Reusable<QRCodeDetector>,
+) -> (Reusable<QRCodeDetector>, Result<DetectionResponse, String>) {
     let start_time = Instant::now();
-    
-    // 图像解码时间统计
     let decode_start = Instant::now();
-    
-    // 解码base64数据
-    let image_data = BASE64_STANDARD.decode(&base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-    
-    // 转换为OpenCV Mat
-    let mat = Mat::from_slice(&image_data)
-        .map_err(|e| format!("Failed to create Mat from base64 data: {}", e))?;
-    
-    let image = opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR)
-        .map_err(|e| format!("Failed to decode image from base64: {}", e))?;
-    
+
+    let image_data = match BASE64_STANDARD.decode(&base64_data) {
+        Ok(data) => data,
+        Err(e) => return (detector, Err(format!("Failed to decode base64: {}", e))),
+    };
+    let mat = match Mat::from_slice(&image_data) {
+        Ok(mat) => mat,
+        Err(e) => return (detector, Err(format!("Failed to create Mat from base64 data: {}", e))),
+    };
+    let image = match opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR) {
+        Ok(image) => image,
+        Err(e) => return (detector, Err(format!("Failed to decode image from base64: {}", e))),
+    };
+
     let decode_time = decode_start.elapsed().as_secs_f64() * 1000.0;
-    
+
     if image.empty() {
-        return Ok(DetectionResponse {
-            success: false,
-            message: "Invalid image format".to_string(),
-            qrcodes: Vec::new(),
-            count: 0,
-            statistics: DetectionStatistics {
-                image_decode_time_ms: decode_time,
-                detection_time_ms: 0.0,
-                total_time_ms: start_time.elapsed().as_secs_f64() * 1000.0,
-                image_width: 0,
-                image_height: 0,
-                pool_acquisition_time_ms: 0.0,
-            },
-        });
+        return (
+            detector,
+            Ok(DetectionResponse {
+                success: false,
+                message: "Invalid image format".to_string(),
+                qrcodes: Vec::new(),
+                count: 0,
+                statistics: DetectionStatistics {
+                    image_decode_time_ms: decode_time,
+                    detection_time_ms: 0.0,
+                    total_time_ms: start_time.elapsed().as_secs_f64() * 1000.0,
+                    image_width: 0,
+                    image_height: 0,
+                    pool_acquisition_time_ms: 0.0,
+                    backend: effective_backend_kind().label().to_string(),
+                },
+                reassembled: None,
+            }),
+        );
     }
-    
+
     let image_size = image.size().unwrap();
-    
-    // 从对象池获取检测器
-    let pool_start = Instant::now();
-    let pool = get_detector_pool();
-    let mut detector = pool.pull(|| {
-        match QRCodeDetector::new() {
-            Ok(detector) => detector,
-            Err(e) => {
-                error!("Failed to create fallback QRCode detector: {}", e);
-                panic!("Cannot create fallback QRCode detector");
-            }
-        }
-    });
-    let pool_acquisition_time = pool_start.elapsed().as_secs_f64() * 1000.0;
-    
-    // 二维码检测时间统计
+
+    if let Err(e) = validate_decoded_image(&image) {
+        return (detector, Err(e));
+    }
+
     let detection_start = Instant::now();
-    let qrcodes = detector.detect_qr_codes(&image)
-        .map_err(|e| format!("QRCode detection failed: {}", e))?;
+    let (detector, detection_result) = detect_with_panic_guard(detector, &image, parse_payload);
+    let qrcodes = match detection_result {
+        Ok(qrcodes) => qrcodes,
+        Err(e) => return (detector, Err(e)),
+    };
     let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
-    
+
     let total_time = start_time.elapsed().as_secs_f64() * 1000.0;
-    
-    Ok(DetectionResponse {
-        success: true,
-        message: format!("Detected {} QR code(s)", qrcodes.len()),
-        count: qrcodes.len(),
-        qrcodes,
-        statistics: DetectionStatistics {
-            image_decode_time_ms: decode_time,
-            detection_time_ms: detection_time,
-            total_time_ms: total_time,
-            image_width: image_size.width,
-            image_height: image_size.height,
-            pool_acquisition_time_ms: pool_acquisition_time,
-        },
-    })
+    let reassembled = reassemble_qrcodes(&qrcodes);
+
+    (
+        detector,
+        Ok(DetectionResponse {
+            success: true,
+            message: format!("Detected {} QR code(s)", qrcodes.len()),
+            count: qrcodes.len(),
+            qrcodes,
+            statistics: DetectionStatistics {
+                image_decode_time_ms: decode_time,
+                detection_time_ms: detection_time,
+                total_time_ms: total_time,
+                image_width: image_size.width,
+                image_height: image_size.height,
+                pool_acquisition_time_ms: 0.0,
+                backend: effective_backend_kind().label().to_string(),
+            },
+            reassembled,
+        }),
+    )
 }
 
-// 从二进制数据检测QR码的辅助函数
-async fn detect_qr_from_binary(binary_data: Vec<u8>) -> Result<DetectionResponse, String> {
+// 二进制帧版本，逻辑与 `detect_qr_from_base64_leased` 相同
+fn detect_qr_from_binary_leased(
+    binary_data: Vec<u8>,
+    detector: Reusable<QRCodeDetector>,
+) -> (Reusable<QRCodeDetector>, Result<DetectionResponse, String>) {
     let start_time = Instant::now();
-    
-    // 图像解码时间统计
     let decode_start = Instant::now();
-    
-    // 转换为OpenCV Mat
-    let mat = Mat::from_slice(&binary_data)
-        .map_err(|e| format!("Failed to create Mat from binary data: {}", e))?;
-    
-    let image = opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR)
-        .map_err(|e| format!("Failed to decode image from binary data: {}", e))?;
-    
+
+    let mat = match Mat::from_slice(&binary_data) {
+        Ok(mat) => mat,
+        Err(e) => return (detector, Err(format!("Failed to create Mat from binary data: {}", e))),
+    };
+    let image = match opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR) {
+        Ok(image) => image,
+        Err(e) => return (detector, Err(format!("Failed to decode image from binary data: {}", e))),
+    };
+
     let decode_time = decode_start.elapsed().as_secs_f64() * 1000.0;
-    
+
     if image.empty() {
-        return Ok(DetectionResponse {
-            success: false,
-            message: "Invalid image format".to_string(),
-            qrcodes: Vec::new(),
-            count: 0,
+        return (
+            detector,
+            Ok(DetectionResponse {
+                success: false,
+                message: "Invalid image format".to_string(),
+                qrcodes: Vec::new(),
+                count: 0,
+                statistics: DetectionStatistics {
+                    image_decode_time_ms: decode_time,
+                    detection_time_ms: 0.0,
+                    total_time_ms: start_time.elapsed().as_secs_f64() * 1000.0,
+                    image_width: 0,
+                    image_height: 0,
+                    pool_acquisition_time_ms: 0.0,
+                    backend: effective_backend_kind().label().to_string(),
+                },
+                reassembled: None,
+            }),
+        );
+    }
+
+    let image_size = image.size().unwrap();
+
+    if let Err(e) = validate_decoded_image(&image) {
+        return (detector, Err(e));
+    }
+
+    let detection_start = Instant::now();
+    let (detector, detection_result) = detect_with_panic_guard(detector, &image, false);
+    let qrcodes = match detection_result {
+        Ok(qrcodes) => qrcodes,
+        Err(e) => return (detector, Err(e)),
+    };
+    let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
+
+    let total_time = start_time.elapsed().as_secs_f64() * 1000.0;
+    let reassembled = reassemble_qrcodes(&qrcodes);
+
+    (
+        detector,
+        Ok(DetectionResponse {
+            success: true,
+            message: format!("Detected {} QR code(s)", qrcodes.len()),
+            count: qrcodes.len(),
+            qrcodes,
             statistics: DetectionStatistics {
                 image_decode_time_ms: decode_time,
-                detection_time_ms: 0.0,
-                total_time_ms: start_time.elapsed().as_secs_f64() * 1000.0,
-                image_width: 0,
-                image_height: 0,
+                detection_time_ms: detection_time,
+                total_time_ms: total_time,
+                image_width: image_size.width,
+                image_height: image_size.height,
                 pool_acquisition_time_ms: 0.0,
+                backend: effective_backend_kind().label().to_string(),
             },
-        });
-    }
-    
-    let image_size = image.size().unwrap();
-    
-    // 从对象池获取检测器
-    let pool_start = Instant::now();
-    let pool = get_detector_pool();
-    let mut detector = pool.pull(|| {
-        match QRCodeDetector::new() {
-            Ok(detector) => detector,
-            Err(e) => {
-                error!("Failed to create fallback QRCode detector: {}", e);
-                panic!("Cannot create fallback QRCode detector");
+            reassembled,
+        }),
+    )
+}
+
+// `qrcode-server scan [--device /dev/video0] [--timeout 30]`：不起 HTTP 服务，直接在命令行里
+// 对着本机摄像头扫一次，方便在无头环境里验证摄像头/驱动能不能解出码
+fn run_scan_subcommand(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut device = default_scan_device();
+    let mut timeout_secs: u64 = default_scan_stream_timeout_secs();
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--device" => {
+                i += 1;
+                if let Some(value) = args.get(i) {
+                    device = value.clone();
+                }
+            }
+            "--timeout" => {
+                i += 1;
+                if let Some(value) = args.get(i).and_then(|v| v.parse::<u64>().ok()) {
+                    timeout_secs = value;
+                }
             }
+            other => eprintln!("Unknown argument: {}", other),
+        }
+        i += 1;
+    }
+
+    info!("Scanning {} for up to {}s...", device, timeout_secs);
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+
+    let result = scan::scan_until(&device, timeout, |frame_bytes| {
+        let mat = Mat::from_slice(&frame_bytes).ok()?;
+        let image = opencv::imgcodecs::imdecode(&mat, IMREAD_COLOR).ok()?;
+        if image.empty() || validate_decoded_image(&image).is_err() {
+            return None;
+        }
+
+        let mut detector = QRCodeDetector::new().ok()?;
+        let qrcodes = detector.detect_qr_codes(&image, true).ok()?;
+        if qrcodes.is_empty() {
+            None
+        } else {
+            Some(qrcodes)
         }
     });
-    let pool_acquisition_time = pool_start.elapsed().as_secs_f64() * 1000.0;
-    
-    // 二维码检测时间统计
-    let detection_start = Instant::now();
-    let qrcodes = detector.detect_qr_codes(&image)
-        .map_err(|e| format!("QRCode detection failed: {}", e))?;
-    let detection_time = detection_start.elapsed().as_secs_f64() * 1000.0;
-    
-    let total_time = start_time.elapsed().as_secs_f64() * 1000.0;
-    
-    Ok(DetectionResponse {
-        success: true,
-        message: format!("Detected {} QR code(s)", qrcodes.len()),
-        count: qrcodes.len(),
-        qrcodes,
-        statistics: DetectionStatistics {
-            image_decode_time_ms: decode_time,
-            detection_time_ms: detection_time,
-            total_time_ms: total_time,
-            image_width: image_size.width,
-            image_height: image_size.height,
-            pool_acquisition_time_ms: pool_acquisition_time,
-        },
-    })
+
+    match result {
+        Ok(qrcodes) => {
+            println!("{}", serde_json::to_string_pretty(&qrcodes)?);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Scan failed: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 初始化日志
     tracing_subscriber::fmt::init();
-    
+
+    let cli_args: Vec<String> = std::env::args().collect();
+    if cli_args.get(1).map(String::as_str) == Some("scan") {
+        return run_scan_subcommand(&cli_args[2..]);
+    }
+
     info!("Starting QRCode detection server...");
     
     // 获取端口配置
@@ -906,8 +1910,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("  Context path: {}", context_path);
     info!("  Pool initial size: {}", initial_pool_size);
     info!("  Pool max size: {}", max_pool_size);
-    
-    // 检查WeChat QRCode模型文件是否存在
+    info!("  Detector backend: {:?} (requested: {:?})", effective_backend_kind(), detector_backend_kind());
+    info!("  Detection timeout: {:?}", detection_timeout());
+
+    // 报告实际生效的检测后端（WeChat 模型文件缺失时已自动降级到 rqrr，不会因此启动失败）
     check_model_files().map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
     
     // 初始化对象池
@@ -925,6 +1931,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .route("/health", get(health_check))
             .route("/detect/file", post(detect_from_file))
             .route("/detect/base64", post(detect_from_base64))
+            .route("/detect/batch", post(detect_batch_handler))
+            .route("/generate", post(generate_handler))
+            .route("/encode", post(encode_handler))
+            .route("/scan/camera", get(scan_camera_handler))
+            .route("/scan", get(scan_stream_handler))
+            .route("/session", post(create_session_handler))
+            .route("/session/:token", get(session_status_handler))
+            .route("/session/:token/scan", post(session_scan_handler))
+            .route("/session/:token/confirm", post(session_confirm_handler))
     } else {
         // 自定义上下文路径
         let context_clone = context_path.clone();
@@ -935,6 +1950,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .route(&format!("{}/health", context_path), get(health_check))
             .route(&format!("{}/detect/file", context_path), post(detect_from_file))
             .route(&format!("{}/detect/base64", context_path), post(detect_from_base64))
+            .route(&format!("{}/detect/batch", context_path), post(detect_batch_handler))
+            .route(&format!("{}/generate", context_path), post(generate_handler))
+            .route(&format!("{}/encode", context_path), post(encode_handler))
+            .route(&format!("{}/scan/camera", context_path), get(scan_camera_handler))
+            .route(&format!("{}/scan", context_path), get(scan_stream_handler))
+            .route(&format!("{}/session", context_path), post(create_session_handler))
+            .route(&format!("{}/session/:token", context_path), get(session_status_handler))
+            .route(&format!("{}/session/:token/scan", context_path), post(session_scan_handler))
+            .route(&format!("{}/session/:token/confirm", context_path), post(session_confirm_handler))
             // 添加根路径重定向到上下文路径
             .route("/", get(move || async move {
                 Redirect::permanent(&format!("{}/", context_clone))
@@ -942,7 +1966,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     
     let app = app.layer(CorsLayer::permissive());
-    
+
+    // 后台定时清理过期的扫码登录会话，避免内存中的存储无限增长
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            session::sweep_expired();
+        }
+    });
+
     let addr = format!("0.0.0.0:{}", port);
     
     info!("Server starting on {}", addr);
@@ -0,0 +1,182 @@
+// 二维码内容分类：把解码出的原始字符串识别成结构化类型，便于客户端直接消费
+use base64::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParsedPayload {
+    Url { url: String },
+    Wifi {
+        ssid: String,
+        auth: String,
+        password: String,
+        hidden: bool,
+    },
+    GeoLocation { lat: f64, lon: f64 },
+    Tel { number: String },
+    Sms { number: String, body: String },
+    MailTo { address: String, subject: Option<String>, body: Option<String> },
+    VCard { fields: HashMap<String, String> },
+    OtpAuth { otp_type: String, label: String, secret: Option<String>, issuer: Option<String> },
+    Text { text: String },
+    Bytes { base64: String },
+}
+
+/// 解析解码出的文本，识别出已知的二维码内容 scheme；无法识别的落到 `Text`
+pub fn parse_payload(text: &str) -> ParsedPayload {
+    let lower = text.to_ascii_lowercase();
+
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        return ParsedPayload::Url { url: text.to_string() };
+    }
+
+    if lower.starts_with("wifi:") {
+        return parse_wifi(text);
+    }
+
+    if lower.starts_with("geo:") {
+        if let Some(geo) = parse_geo(text) {
+            return geo;
+        }
+    }
+
+    if lower.starts_with("tel:") {
+        return ParsedPayload::Tel { number: text[4..].to_string() };
+    }
+
+    if lower.starts_with("smsto:") || lower.starts_with("sms:") {
+        return parse_sms(text);
+    }
+
+    if lower.starts_with("mailto:") {
+        return parse_mailto(text);
+    }
+
+    if lower.starts_with("mecard:") || lower.starts_with("begin:vcard") {
+        return ParsedPayload::VCard { fields: parse_vcard_fields(text) };
+    }
+
+    if lower.starts_with("otpauth://") {
+        return parse_otpauth(text);
+    }
+
+    ParsedPayload::Text { text: text.to_string() }
+}
+
+/// 解码原始字节：能以 UTF-8 解释则按文本走分类器，否则 base64 编码原始字节返回
+pub fn parse_payload_bytes(bytes: &[u8]) -> ParsedPayload {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => parse_payload(text),
+        Err(_) => ParsedPayload::Bytes { base64: BASE64_STANDARD.encode(bytes) },
+    }
+}
+
+fn field(segments: &[&str], key: &str) -> Option<String> {
+    segments.iter().find_map(|segment| {
+        segment.strip_prefix(key).map(|rest| rest.to_string())
+    })
+}
+
+// WIFI:S:<ssid>;T:<auth>;P:<password>;H:<hidden>;;
+fn parse_wifi(text: &str) -> ParsedPayload {
+    let body = text.splitn(2, ':').nth(1).unwrap_or("");
+    let segments: Vec<&str> = body.split(';').collect();
+
+    ParsedPayload::Wifi {
+        ssid: field(&segments, "S:").unwrap_or_default(),
+        auth: field(&segments, "T:").unwrap_or_else(|| "nopass".to_string()),
+        password: field(&segments, "P:").unwrap_or_default(),
+        hidden: field(&segments, "H:").map(|h| h.eq_ignore_ascii_case("true")).unwrap_or(false),
+    }
+}
+
+// geo:<lat>,<lon>
+fn parse_geo(text: &str) -> Option<ParsedPayload> {
+    let body = text.splitn(2, ':').nth(1)?;
+    let body = body.split(';').next().unwrap_or(body);
+    let mut parts = body.split(',');
+    let lat: f64 = parts.next()?.trim().parse().ok()?;
+    let lon: f64 = parts.next()?.trim().parse().ok()?;
+    Some(ParsedPayload::GeoLocation { lat, lon })
+}
+
+// SMSTO:<number>:<body>  or  sms:<number>?body=<body>
+fn parse_sms(text: &str) -> ParsedPayload {
+    let body_start = text.find(':').map(|i| i + 1).unwrap_or(text.len());
+    let rest = &text[body_start..];
+    let colon = rest.find(':');
+    if let Some(q) = rest.find('?').filter(|&q| colon.map_or(true, |c| q < c)) {
+        let body = rest[q + 1..].split('&').find_map(|pair| pair.strip_prefix("body=")).unwrap_or("");
+        return ParsedPayload::Sms { number: rest[..q].to_string(), body: body.to_string() };
+    }
+    if let Some((number, body)) = rest.split_once(':') {
+        ParsedPayload::Sms { number: number.to_string(), body: body.to_string() }
+    } else {
+        ParsedPayload::Sms { number: rest.to_string(), body: String::new() }
+    }
+}
+
+// mailto:<address>?subject=<subject>&body=<body>
+fn parse_mailto(text: &str) -> ParsedPayload {
+    let rest = &text[7..];
+    let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut subject = None;
+    let mut body = None;
+    for pair in query.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            match key {
+                "subject" => subject = Some(value.to_string()),
+                "body" => body = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    ParsedPayload::MailTo { address: address.to_string(), subject, body }
+}
+
+// otpauth://TYPE/LABEL?secret=...&issuer=...  (Google Authenticator 的 TOTP/HOTP key URI)
+fn parse_otpauth(text: &str) -> ParsedPayload {
+    let rest = &text["otpauth://".len()..];
+    let (otp_type, path_and_query) = rest.split_once('/').unwrap_or((rest, ""));
+    let (label, query) = path_and_query.split_once('?').unwrap_or((path_and_query, ""));
+
+    let mut secret = None;
+    let mut issuer = None;
+    for pair in query.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            match key {
+                "secret" => secret = Some(value.to_string()),
+                "issuer" => issuer = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    ParsedPayload::OtpAuth {
+        otp_type: otp_type.to_string(),
+        label: label.to_string(),
+        secret,
+        issuer,
+    }
+}
+
+// MECARD:N:<name>;TEL:<tel>;;  或 vCard 的 KEY:VALUE 行格式，统一抽成 key/value map
+fn parse_vcard_fields(text: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let body = text.splitn(2, ':').nth(1).unwrap_or(text);
+
+    for entry in body.split(|c| c == ';' || c == '\n') {
+        let entry = entry.trim();
+        if entry.is_empty() || entry.eq_ignore_ascii_case("END:VCARD") {
+            continue;
+        }
+        if let Some((key, value)) = entry.split_once(':') {
+            fields.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    fields
+}
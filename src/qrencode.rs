@@ -0,0 +1,251 @@
+// 基于 OpenCV QRCodeEncoder 的二维码生成：在纯 Rust 的 `generate` 模块之外，提供一条镜像
+// 解码管线（同样走 OpenCV）的编码路径，支持编码模式选择、指定版本号/边框，以及
+// Structured Append 长文本分片——扫描端可以用已有的多码检测逻辑把分片重新拼起来。
+use base64::prelude::*;
+use opencv::{
+    core::{Mat, Scalar, Vector},
+    imgcodecs,
+    objdetect::{QRCodeEncoder, QRCodeEncoder_CorrectionLevel, QRCodeEncoder_EncodeMode, QRCodeEncoder_Params},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+/// 标准二维码纠错等级：L(7%) < M(15%) < Q(25%) < H(30%)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ErrorCorrection {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl Default for ErrorCorrection {
+    fn default() -> Self {
+        ErrorCorrection::L
+    }
+}
+
+impl From<ErrorCorrection> for QRCodeEncoder_CorrectionLevel {
+    fn from(value: ErrorCorrection) -> Self {
+        match value {
+            ErrorCorrection::L => QRCodeEncoder_CorrectionLevel::CORRECT_LEVEL_L,
+            ErrorCorrection::M => QRCodeEncoder_CorrectionLevel::CORRECT_LEVEL_M,
+            ErrorCorrection::Q => QRCodeEncoder_CorrectionLevel::CORRECT_LEVEL_Q,
+            ErrorCorrection::H => QRCodeEncoder_CorrectionLevel::CORRECT_LEVEL_H,
+        }
+    }
+}
+
+/// 镜像 OpenCV `QRCodeEncoder::EncodeMode`，让数字/字母数字载荷能打包得更紧凑，
+/// 长文本可以用 `StructuredAppend` 拆成一组符号
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EncodeMode {
+    Auto,
+    Numeric,
+    Alphanumeric,
+    Byte,
+    Kanji,
+    Eci,
+    StructuredAppend,
+}
+
+impl Default for EncodeMode {
+    fn default() -> Self {
+        EncodeMode::Auto
+    }
+}
+
+impl From<EncodeMode> for QRCodeEncoder_EncodeMode {
+    fn from(value: EncodeMode) -> Self {
+        match value {
+            EncodeMode::Auto => QRCodeEncoder_EncodeMode::MODE_AUTO,
+            EncodeMode::Numeric => QRCodeEncoder_EncodeMode::MODE_NUMERIC,
+            EncodeMode::Alphanumeric => QRCodeEncoder_EncodeMode::MODE_ALPHANUMERIC,
+            EncodeMode::Byte => QRCodeEncoder_EncodeMode::MODE_BYTE,
+            EncodeMode::Kanji => QRCodeEncoder_EncodeMode::MODE_KANJI,
+            EncodeMode::Eci => QRCodeEncoder_EncodeMode::MODE_ECI,
+            EncodeMode::StructuredAppend => QRCodeEncoder_EncodeMode::MODE_STRUCTURED_APPEND,
+        }
+    }
+}
+
+fn default_border() -> u32 {
+    4
+}
+
+fn default_parts() -> i32 {
+    2
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncodeRequest {
+    pub text: String,
+    #[serde(default)]
+    pub error_correction: ErrorCorrection,
+    #[serde(default)]
+    pub mode: EncodeMode,
+    /// 二维码版本（1-40），不指定时由 OpenCV 自动选择能容纳内容的最小版本
+    pub version: Option<i32>,
+    #[serde(default = "default_border")]
+    pub border: u32,
+    /// 仅在 `mode = STRUCTURED_APPEND` 时生效：要拆成几张符号
+    #[serde(default = "default_parts")]
+    pub parts: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncodeResponse {
+    pub success: bool,
+    pub message: String,
+    pub content_type: String,
+    pub image_base64: Option<String>,
+    /// 仅在 `mode = STRUCTURED_APPEND` 时填充：按分片顺序排列的 PNG base64 列表
+    pub parts_base64: Option<Vec<String>>,
+}
+
+/// 渲染结果：字节内容 + 用于 HTTP 响应的 content-type
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+fn build_params(req: &EncodeRequest) -> opencv::Result<QRCodeEncoder_Params> {
+    let mut params = QRCodeEncoder_Params::default()?;
+    params.version = req.version.unwrap_or(0);
+    params.correction_level = req.error_correction.into();
+    params.mode = req.mode.into();
+    Ok(params)
+}
+
+// OpenCV 的 QRCodeEncoder 只画纯符号，不留静区，所以边框（quiet zone）自己用白色常量边框补上
+fn add_quiet_zone(qrcode: &Mat, border: u32) -> opencv::Result<Mat> {
+    let mut bordered = Mat::default();
+    let border = border as i32;
+    opencv::core::copy_make_border(
+        qrcode,
+        &mut bordered,
+        border,
+        border,
+        border,
+        border,
+        opencv::core::BORDER_CONSTANT,
+        Scalar::all(255.0),
+    )?;
+    Ok(bordered)
+}
+
+fn encode_mat_to_png(mat: &Mat) -> Result<Vec<u8>, String> {
+    let mut buf = Vector::<u8>::new();
+    imgcodecs::imencode(".png", mat, &mut buf, &Vector::new())
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(buf.to_vec())
+}
+
+/// 生成单张二维码并渲染为 PNG 字节
+pub fn render_png(req: &EncodeRequest) -> Result<EncodedImage, String> {
+    let params = build_params(req).map_err(|e| format!("Failed to build encoder params: {}", e))?;
+    let mut encoder = <dyn QRCodeEncoder>::create(&params)
+        .map_err(|e| format!("Failed to create QRCodeEncoder: {}", e))?;
+
+    let mut qrcode = Mat::default();
+    encoder
+        .encode(&req.text, &mut qrcode)
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+
+    let bordered = add_quiet_zone(&qrcode, req.border).map_err(|e| format!("Failed to add quiet zone: {}", e))?;
+
+    Ok(EncodedImage {
+        bytes: encode_mat_to_png(&bordered)?,
+        content_type: "image/png",
+    })
+}
+
+// 把文本按字符边界切成大致相等的 N 份，供 Structured Append 逐片编码
+fn split_into_chunks(text: &str, parts: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let parts = parts.max(1);
+    let base = chars.len() / parts;
+    let extra = chars.len() % parts;
+
+    // 各份长度至多相差 1；字符数少于 N 时末尾几份为空串，份数始终是 N
+    let mut chunks = Vec::with_capacity(parts);
+    let mut start = 0;
+    for i in 0..parts {
+        let len = base + usize::from(i < extra);
+        chunks.push(chars[start..start + len].iter().collect());
+        start += len;
+    }
+    chunks
+}
+
+/// ISO 18004 本身把一组 Structured Append 符号的数量上限定在 16；`req.parts` 来自客户端，
+/// 不做上限校验的话一个 `parts: 2000000000` 的请求就会在 `split_into_chunks` 里立刻
+/// 分配一个二十亿元素的 `Vec<String>`（还没算后面逐张渲染 PNG 的开销）
+const MAX_STRUCTURED_APPEND_PARTS: i32 = 16;
+
+fn validate_parts(parts: i32) -> Result<usize, String> {
+    if parts < 1 || parts > MAX_STRUCTURED_APPEND_PARTS {
+        return Err(format!(
+            "parts must be between 1 and {} (ISO 18004 structured-append symbol limit)",
+            MAX_STRUCTURED_APPEND_PARTS
+        ));
+    }
+    Ok(parts as usize)
+}
+
+/// Structured Append：文本装不下单个符号时，按 `req.parts` 拆成一组二维码。
+///
+/// 这里没有用 OpenCV `QRCodeEncoder::encode_structured_append` 原生的多符号编码——它按
+/// ISO 18004 写入的序号/总数/奇偶校验位在解码时读不回来（见 `structured_append` 模块开头的
+/// 说明），拼回去就无从谈起。于是改成自己切片，在每片前面加一段 `structured_append::frame`
+/// 写的自定义头部，再各自当作普通单符号二维码编码（BYTE 模式，避免跟头部里的分隔符冲突）；
+/// 扫描端用 `structured_append::reassemble` 识别并拼接这个头部。
+pub fn render_structured_append(req: &EncodeRequest) -> Result<Vec<EncodedImage>, String> {
+    let parts = validate_parts(req.parts)?;
+    let chunks = split_into_chunks(&req.text, parts);
+    let total = chunks.len();
+
+    let mut images = Vec::new();
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let framed = crate::structured_append::frame(index, total, &chunk);
+        let part_req = EncodeRequest {
+            text: framed,
+            mode: EncodeMode::Byte,
+            ..req.clone()
+        };
+        images.push(render_png(&part_req)?);
+    }
+
+    Ok(images)
+}
+
+/// 按 `req.mode` 分发：`STRUCTURED_APPEND` 产出多张分片图，其它模式产出单张图
+pub fn encode(req: &EncodeRequest) -> Result<EncodeResponse, String> {
+    if req.mode == EncodeMode::StructuredAppend {
+        let images = render_structured_append(req)?;
+        return Ok(EncodeResponse {
+            success: true,
+            message: format!(
+                "QR code split into {} structured-append part(s). These parts carry a header private to \
+                 this service and can only be reassembled by its own /detect/file, /detect/base64, \
+                 /detect/batch (across a batch's images too) and /scan/camera endpoints -- third-party scanners and \
+                 encoders won't recognize or reassemble them.",
+                images.len()
+            ),
+            content_type: "image/png".to_string(),
+            image_base64: None,
+            parts_base64: Some(images.into_iter().map(|img| BASE64_STANDARD.encode(&img.bytes)).collect()),
+        });
+    }
+
+    let rendered = render_png(req)?;
+    Ok(EncodeResponse {
+        success: true,
+        message: "QR code encoded".to_string(),
+        content_type: rendered.content_type.to_string(),
+        image_base64: Some(BASE64_STANDARD.encode(&rendered.bytes)),
+        parts_base64: None,
+    })
+}
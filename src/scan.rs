@@ -0,0 +1,126 @@
+// V4L2 本地摄像头抓帧：让部署在 kiosk/嵌入式主机上的服务能直接扫描物理摄像头，
+// 而不仅仅是中转浏览器帧（见 camera_qr_scanner.html）。
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use v4l::buffer::Type;
+use v4l::io::traits::CaptureStream;
+use v4l::prelude::*;
+use v4l::video::Capture;
+use v4l::FourCC;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum CameraError {
+    DeviceOpenFailed { device: String, reason: String },
+    FormatMismatch { expected: String, actual: String },
+    CaptureFailed { reason: String },
+    Timeout { timeout_ms: u64 },
+}
+
+impl std::fmt::Display for CameraError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CameraError::DeviceOpenFailed { device, reason } => {
+                write!(f, "Failed to open camera device {}: {}", device, reason)
+            }
+            CameraError::FormatMismatch { expected, actual } => {
+                write!(f, "Camera pixel format mismatch: expected {}, got {}", expected, actual)
+            }
+            CameraError::CaptureFailed { reason } => write!(f, "Camera capture failed: {}", reason),
+            CameraError::Timeout { timeout_ms } => write!(f, "Camera scan timed out after {}ms", timeout_ms),
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+/// 打开设备并请求 MJPG 格式（可直接喂给 `imdecode`），格式不匹配时报告实际格式而不是静默继续。
+/// 返回裸 `Device`，留给调用方决定生命周期——`MmapStream` 要借用它，不能让它在这一步就被丢弃。
+fn open_device(device_path: &str) -> Result<Device, CameraError> {
+    let dev = Device::with_path(device_path).map_err(|e| CameraError::DeviceOpenFailed {
+        device: device_path.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    let mut format = dev.format().map_err(|e| CameraError::DeviceOpenFailed {
+        device: device_path.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    let requested = FourCC::new(b"MJPG");
+    format.fourcc = requested;
+    let actual_format = dev.set_format(&format).map_err(|e| CameraError::DeviceOpenFailed {
+        device: device_path.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    if actual_format.fourcc != requested {
+        return Err(CameraError::FormatMismatch {
+            expected: requested.to_string(),
+            actual: actual_format.fourcc.to_string(),
+        });
+    }
+
+    Ok(dev)
+}
+
+/// 一个 V4L2 设备上的抓帧流，每次 `grab_frame` 取一帧。
+///
+/// `MmapStream<'a>` 借用着它背后的 `Device`，这个 `Device` 由调用方持有，生命周期
+/// `'a` 保证它在 `Self` 存活期间不会被析构；结构体本身不持有 `Device`。
+pub struct CameraFrameSource<'a> {
+    stream: MmapStream<'a>,
+    device_name: String,
+}
+
+impl<'a> CameraFrameSource<'a> {
+    /// 基于一个调用方持有的 `Device` 建立抓帧流，`dev` 必须在 `Self` 存活期间一直有效
+    pub fn from_device(dev: &'a Device, device_path: &str) -> Result<Self, CameraError> {
+        let stream = MmapStream::with_buffers(dev, Type::VideoCapture, 4)
+            .map_err(|e| CameraError::CaptureFailed { reason: e.to_string() })?;
+
+        Ok(Self {
+            stream,
+            device_name: device_path.to_string(),
+        })
+    }
+
+    /// 抓取一帧 MJPG 编码的数据，调用方可以直接交给 `opencv::imgcodecs::imdecode`
+    pub fn grab_frame(&mut self) -> Result<Vec<u8>, CameraError> {
+        let (buffer, _meta) = self.stream.next().map_err(|e| CameraError::CaptureFailed {
+            reason: format!("device {}: {}", self.device_name, e),
+        })?;
+        Ok(buffer.to_vec())
+    }
+}
+
+/// 打开设备并校验像素格式，返回 `Device` 和设备路径；抓帧流不在这里建立，由调用方在
+/// 持有 `Device` 的同一作用域里用 `CameraFrameSource::from_device` 建立
+pub fn open(device_path: &str) -> Result<(Device, String), CameraError> {
+    let dev = open_device(device_path)?;
+    Ok((dev, device_path.to_string()))
+}
+
+/// 驱动抓帧循环：对每一帧调用 `on_frame`，第一次返回 `Some` 即停止并返回结果，
+/// 超过 `timeout` 仍未命中则返回 `CameraError::Timeout`。
+/// `Device` 在这个函数的整个循环期间都存活，`CameraFrameSource` 借用它是安全的。
+pub fn scan_until<T>(
+    device_path: &str,
+    timeout: Duration,
+    mut on_frame: impl FnMut(Vec<u8>) -> Option<T>,
+) -> Result<T, CameraError> {
+    let (dev, device_name) = open(device_path)?;
+    let mut source = CameraFrameSource::from_device(&dev, &device_name)?;
+    let start = Instant::now();
+
+    loop {
+        if start.elapsed() > timeout {
+            return Err(CameraError::Timeout { timeout_ms: timeout.as_millis() as u64 });
+        }
+
+        let frame = source.grab_frame()?;
+        if let Some(result) = on_frame(frame) {
+            return Ok(result);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// 扫码登录会话：短生命周期的 token 状态机，供「扫码登录/设备配对」类客户端使用。
+// 状态只会单向前进 NotScanned -> Scanned -> Confirmed，过期的会话在读取/流转时惰性折叠为 Expired。
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    NotScanned,
+    Scanned,
+    Confirmed,
+    Expired,
+}
+
+#[derive(Debug)]
+struct Session {
+    state: SessionState,
+    created_at: Instant,
+    ttl: Duration,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionStatus {
+    pub token: String,
+    pub state: SessionState,
+}
+
+fn store() -> &'static Mutex<HashMap<String, Session>> {
+    static STORE: OnceLock<Mutex<HashMap<String, Session>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// 会话存活时间，可通过 SESSION_TTL_SECS 覆盖，默认 2 分钟
+fn session_ttl() -> Duration {
+    std::env::var("SESSION_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(120))
+}
+
+fn generate_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..32)
+        .map(|_| std::char::from_digit(rng.gen_range(0..36), 36).unwrap())
+        .collect()
+}
+
+fn is_expired(session: &Session) -> bool {
+    session.created_at.elapsed() > session.ttl
+}
+
+fn to_status(token: &str, session: &Session) -> SessionStatus {
+    SessionStatus {
+        token: token.to_string(),
+        state: session.state,
+    }
+}
+
+/// 创建一个新会话并记录 `NotScanned` 状态，返回分配的 token
+pub fn create() -> String {
+    let token = generate_token();
+    let mut sessions = store().lock().unwrap();
+    sessions.insert(
+        token.clone(),
+        Session {
+            state: SessionState::NotScanned,
+            created_at: Instant::now(),
+            ttl: session_ttl(),
+        },
+    );
+    token
+}
+
+/// 读取当前状态；`None` 表示 token 不存在
+pub fn status(token: &str) -> Option<SessionStatus> {
+    let mut sessions = store().lock().unwrap();
+    let session = sessions.get_mut(token)?;
+    if is_expired(session) {
+        session.state = SessionState::Expired;
+    }
+    Some(to_status(token, session))
+}
+
+/// 扫码端上报已扫描：仅在当前状态为 `NotScanned` 时推进到 `Scanned`
+pub fn mark_scanned(token: &str) -> Option<SessionStatus> {
+    transition(token, SessionState::NotScanned, SessionState::Scanned)
+}
+
+/// 扫码端确认登录：仅在当前状态为 `Scanned` 时推进到 `Confirmed`
+pub fn confirm(token: &str) -> Option<SessionStatus> {
+    transition(token, SessionState::Scanned, SessionState::Confirmed)
+}
+
+fn transition(token: &str, from: SessionState, to: SessionState) -> Option<SessionStatus> {
+    let mut sessions = store().lock().unwrap();
+    let session = sessions.get_mut(token)?;
+    if is_expired(session) {
+        session.state = SessionState::Expired;
+    } else if session.state == from {
+        session.state = to;
+    }
+    Some(to_status(token, session))
+}
+
+/// 清理已过期的会话，避免内存里的存储无限增长；供后台定时任务调用
+pub fn sweep_expired() {
+    let mut sessions = store().lock().unwrap();
+    sessions.retain(|_, session| !is_expired(session));
+}
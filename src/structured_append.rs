@@ -0,0 +1,74 @@
+// Structured Append 重组：把一组分片二维码的解码文本按顺序拼回完整内容。
+//
+// ISO/IEC 18004 的 Structured Append 头（符号序号、总数、共享奇偶校验字节）是二维码
+// 比特流里模式指示符之后的几个比特，`opencv::objdetect::QRCodeDetector::detect_and_decode`
+// 只返回解码完的最终字符串，这几位头信息在这一层已经被库吃掉、读不回来了——WeChat/classic
+// 后端的 Rust 绑定都没有暴露它。所以这里不去猜测/反推真实的 ISO 头比特，而是约定一套自己
+// 可控的轻量文本头：`SA|<index>|<total>|<parity_hex>|<payload>`，由 `qrencode::render_structured_append`
+// 在编码时写入。只有经由本服务 `/encode`（`mode = STRUCTURED_APPEND`）生成的二维码才带这个头，
+// 因此也只有这些码能在这里被重新拼起来；第三方设备/应用生成的 Structured Append 二维码不带
+// 这个头，自然无法重组。
+const PREFIX: &str = "SA";
+
+struct Fragment {
+    index: usize,
+    total: usize,
+    parity: u8,
+    payload: String,
+}
+
+fn parity_of(payload: &str) -> u8 {
+    payload.bytes().fold(0u8, |acc, b| acc ^ b)
+}
+
+/// 给一个分片的原始内容打上可解析的头部
+pub fn frame(index: usize, total: usize, payload: &str) -> String {
+    format!("{}|{}|{}|{:02x}|{}", PREFIX, index, total, parity_of(payload), payload)
+}
+
+fn parse_fragment(text: &str) -> Option<Fragment> {
+    let rest = text.strip_prefix(PREFIX)?.strip_prefix('|')?;
+    let mut parts = rest.splitn(4, '|');
+    let index: usize = parts.next()?.parse().ok()?;
+    let total: usize = parts.next()?.parse().ok()?;
+    let parity = u8::from_str_radix(parts.next()?, 16).ok()?;
+    let payload = parts.next()?.to_string();
+    Some(Fragment { index, total, parity, payload })
+}
+
+/// 一次重组尝试的结果：要么拼出了完整内容，要么缺了哪些序号一清二楚
+pub enum Outcome {
+    Complete(String),
+    Incomplete { total: usize, missing: Vec<usize> },
+}
+
+/// 在一组解码文本里找出属于同一个 Structured Append 序列的分片并尝试拼接。
+/// 不带本服务头部的文本会被忽略；校验和对不上的分片视为整个序列不可信，直接放弃。
+/// 没有任何分片匹配时返回 `None`（这组码根本不是 Structured Append，不算错误）
+pub fn reassemble(texts: &[String]) -> Option<Outcome> {
+    let fragments: Vec<Fragment> = texts.iter().filter_map(|t| parse_fragment(t)).collect();
+    if fragments.is_empty() {
+        return None;
+    }
+
+    let total = fragments[0].total;
+    if total == 0 || fragments.iter().any(|f| f.total != total) {
+        return None;
+    }
+
+    let mut by_index = std::collections::HashMap::new();
+    for fragment in &fragments {
+        if parity_of(&fragment.payload) != fragment.parity {
+            return None;
+        }
+        by_index.insert(fragment.index, &fragment.payload);
+    }
+
+    let missing: Vec<usize> = (0..total).filter(|i| !by_index.contains_key(i)).collect();
+    if !missing.is_empty() {
+        return Some(Outcome::Incomplete { total, missing });
+    }
+
+    let text = (0..total).map(|i| by_index[&i].clone()).collect::<Vec<_>>().join("");
+    Some(Outcome::Complete(text))
+}